@@ -17,7 +17,7 @@ struct Settings {
 	time: f32,
 	alfa: f32,
 	beta: f32,
-	pad_c: f32
+	hyperspace: f32
 }
 
 @group(1) @binding(4)
@@ -45,7 +45,9 @@ fn fragment(
 	let A = vec2<f32>(1.0,0.0) * dcol.r + vec2<f32>(-0.5,0.5) * dcol.g + vec2<f32>(-0.5,0.5) * dcol.b;
 	//let A = vec2<f32>(0.0,0.0);
 	let duv = vec2<f32>(sin(omega * (1.0 + d_omega) * settings.time) * A.x + 0.1 * A.y, cos(omega * settings.time) * A.y + 0.1 * A.x);
-	let colour = ((1.0 - alfa) * col_1 + alfa * textureSample(texture_2, our_sampler_2, vec2<f32>(uv.x, uv.y) + duv).rgb);
+	// In hyperspace the trails get dragged in towards the middle, so they streak outwards
+	let pull = (vec2<f32>(uv.x, uv.y) - vec2<f32>(0.5, 0.5)) * 0.03 * settings.hyperspace;
+	let colour = ((1.0 - alfa) * col_1 + alfa * textureSample(texture_2, our_sampler_2, vec2<f32>(uv.x, uv.y) + duv - pull).rgb);
 
 	return vec4<f32>(colour, 1.0);
 }
@@ -0,0 +1,38 @@
+#import bevy_sprite::mesh2d_view_bindings
+#import bevy_pbr::utils
+
+@group(1) @binding(0)
+var texture_1: texture_2d<f32>;
+
+@group(1) @binding(1)
+var our_sampler_1: sampler;
+
+@group(1) @binding(2)
+var texture_2: texture_2d<f32>;
+
+@group(1) @binding(3)
+var our_sampler_2: sampler;
+
+struct Settings {
+	max_change: f32,
+	pad_a: f32,
+	pad_b: f32,
+	pad_c: f32,
+}
+
+@group(1) @binding(4)
+var<uniform> settings: Settings;
+
+@fragment
+fn fragment(
+	@builtin(position) position: vec4<f32>,
+	#import bevy_sprite::mesh2d_vertex_output
+) -> @location(0) vec4<f32> {
+	let current = textureSample(texture_1, our_sampler_1, vec2<f32>(uv.x, uv.y)).rgb;
+	let previous = textureSample(texture_2, our_sampler_2, vec2<f32>(uv.x, uv.y)).rgb;
+
+	// Each channel gets limited on its own, so a swap between equally bright colours is held back too
+	let change = clamp(current - previous, vec3<f32>(-settings.max_change), vec3<f32>(settings.max_change));
+
+	return vec4<f32>(previous + change, 1.0);
+}
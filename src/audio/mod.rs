@@ -2,12 +2,13 @@ use std::time::Duration;
 
 use bevy::prelude::*;
 use rand::seq::SliceRandom;
-use crate::{prelude::*, GameState};
+use crate::{prelude::*, player::ShipState, GameState};
 use bevy_kira_audio::prelude::*;
 
 use self::slang::SlangTriggerEvent;
 
 mod slang;
+mod thoughts;
 
 pub struct AudioPlugin;
 
@@ -20,18 +21,26 @@ impl Plugin for AudioPlugin {
 		.add_event::<ButtonPressEvent>()
 		.add_event::<SlangTriggerEvent>()
 		.add_event::<ThoughtCutsceneEndEvent>()
+		.add_event::<BoostEvent>()
 		.add_systems((
 			spawn_player_ship_audio,
 			spawn_music,
 		).in_schedule(OnEnter(GameState::Game)))
+		.add_system(enter_hyperspace_audio.in_schedule(OnEnter(ShipState::Hyperspace)))
+		.add_system(exit_hyperspace_audio.in_schedule(OnExit(ShipState::Hyperspace)))
 		.add_systems((
-			update_player_audio,
 			update_music_volume,
 			update_track,
 			play_button_sounds,
+			play_boost_sounds,
 			slang::clean_up_slang_audio,
 			slang::play_slang_audio,
 			slang::try_trigger_slang,
+			thoughts::play_collected_thought_audio,
+			thoughts::play_nearby_thought_audio.after(crate::physics::InteractionSystemSet),
+			thoughts::forget_played_thoughts,
+			thoughts::update_thought_audio,
+			thoughts::clean_up_thought_audio,
 		).distributive_run_if(in_state(GameState::Game)))
 		;
 	}
@@ -63,26 +72,26 @@ fn spawn_music(mut commands: Commands, audio: Res<Audio>, asset_server: Res<Asse
 	commands.spawn(MusicPlayer {track: 0, handle});
 }
 
-fn update_player_audio(
-	player: Query<&Velocity, With<Player>>,
-	ship_audio: Query<&ShipAudio>,
-	mut audio_instances: ResMut<Assets<AudioInstance>>,
+fn set_ship_audio_volume(
+	volume: f64,
+	ship_audio: &Query<&ShipAudio>,
+	audio_instances: &mut Assets<AudioInstance>,
 ) {
 	if let Ok(ShipAudio(audio_instance)) = ship_audio.get_single() {
 		if let Some(audio_instance) = audio_instances.get_mut(audio_instance) {
-			let volume = if let Ok(velocity) = player.get_single() {
-				let normalized_velocity = velocity.length() / 100.0;
-				if normalized_velocity < 1.0 {
-					0.0
-				} else {
-					normalized_velocity.ln()
-				}
-			} else {0.0};
-			audio_instance.set_volume(volume as f64, AudioTween::default());
+			audio_instance.set_volume(volume, AudioTween::linear(Duration::from_millis(1500)));
 		}
 	}
 }
 
+fn enter_hyperspace_audio(ship_audio: Query<&ShipAudio>, mut audio_instances: ResMut<Assets<AudioInstance>>) {
+	set_ship_audio_volume(1.0, &ship_audio, &mut audio_instances);
+}
+
+fn exit_hyperspace_audio(ship_audio: Query<&ShipAudio>, mut audio_instances: ResMut<Assets<AudioInstance>>) {
+	set_ship_audio_volume(0.0, &ship_audio, &mut audio_instances);
+}
+
 fn update_music_volume(
 	priority_entities: Query<&PrioritySpeaker>,
 	music: Query<&MusicPlayer>,
@@ -129,7 +138,8 @@ fn update_track(
 	mut music: Query<&mut MusicPlayer>,
 	mut audio_instances: ResMut<Assets<AudioInstance>>,
 	audio: Res<Audio>,
-	asset_server: Res<AssetServer>
+	asset_server: Res<AssetServer>,
+	mut rng: ResMut<GameRng>,
 ) {
 	if let Some(score) = score {
 		let value = score.score;
@@ -152,8 +162,8 @@ fn update_track(
 				if let Some(instance) = audio_instances.get_mut(&player.handle) {
 					if instance.state() == PlaybackState::Stopped {
 						// Roll a new track
-						let mut rng = rand::thread_rng();
-						if let Ok(&(track,_)) = JUKEBOX.choose_weighted(&mut rng, |item| item.1) {
+						let rng = rng.stream(RngStream::Jukebox);
+						if let Ok(&(track,_)) = JUKEBOX.choose_weighted(rng, |item| item.1) {
 							let handle = audio
 							.play(asset_server.load(track))
 							.fade_in(AudioTween::linear(Duration::from_secs(1)))
@@ -197,3 +207,13 @@ fn play_button_sounds(
 		}
 	}
 }
+
+fn play_boost_sounds(
+	audio: Res<Audio>,
+	asset_server: Res<AssetServer>,
+	mut boost_events: EventReader<BoostEvent>
+) {
+	for _ in boost_events.iter() {
+		audio.play(asset_server.load("audio/effects/boost.ogg")).with_volume(0.8);
+	}
+}
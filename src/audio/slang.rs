@@ -1,5 +1,5 @@
 use bevy::prelude::*;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use crate::prelude::*;
 use bevy_kira_audio::prelude::*;
 
@@ -13,6 +13,7 @@ pub(super) fn try_trigger_slang(
 	mut thought_collected: EventReader<ThoughtCollectedEvent>,
 	mut thoughts_merged: EventReader<ThoughtCutsceneEndEvent>,
 	mut trigger: EventWriter<SlangTriggerEvent>,
+	mut rng: ResMut<GameRng>,
 ) {
 	if !slang_query.is_empty() {
 		return;
@@ -20,7 +21,7 @@ pub(super) fn try_trigger_slang(
 
 	let mut do_it = false;
 	let mut philo = false;
-	let mut rng = thread_rng();
+	let rng = rng.stream(RngStream::Slang);
 
 	for _ in thought_collected.iter() {
 		if rng.gen_bool(0.075) {
@@ -51,7 +52,8 @@ pub(super) fn play_slang_audio(
 	mut commands: Commands,
 	mut events: EventReader<SlangTriggerEvent>,
 	audio: Res<Audio>,
-	asset_server: Res<AssetServer>
+	asset_server: Res<AssetServer>,
+	mut rng: ResMut<GameRng>,
 ) {
 	if events.is_empty() {
 		return;
@@ -61,7 +63,7 @@ pub(super) fn play_slang_audio(
 
 
 	let tape = {
-		let mut rng = thread_rng();
+		let rng = rng.stream(RngStream::Slang);
 		if philo {
 			let tape_choice = rng.gen::<u32>() % 4;
 			format!("audio/slang/slangg-{:0>2}.ogg", tape_choice+1)
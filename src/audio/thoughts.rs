@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+use crate::{prelude::*, physics::SpatialGrid};
+use bevy_kira_audio::prelude::*;
+
+// Thoughts closer than this start whispering
+const HEARING_RADIUS: f32 = 15.0;
+// Clips fade out completely at this distance
+const AUDIBLE_DISTANCE: f32 = 60.0;
+
+// A thought's clip that is playing somewhere in space
+#[derive(Component)]
+pub(super) struct ThoughtAudio {
+	handle: Handle<AudioInstance>,
+	position: Vec3,
+	// Thought entity the sound follows while it exists
+	source: Option<Entity>,
+}
+
+// Marks thoughts that already played their clip when the ship flew by
+#[derive(Component)]
+pub(super) struct ThoughtAudioPlayed;
+
+// Volume and panning of a sound at the given position, as heard by the player
+fn spatial_mix(listener: &Transform, position: Vec3) -> (f64, f64) {
+	let offset = position - listener.translation;
+	let volume = (1.0 - offset.length() / AUDIBLE_DISTANCE).clamp(0.0, 1.0).powi(2);
+	let panning = if offset.length_squared() > 0.0001 {
+		(offset.normalize().dot(listener.right()) + 1.0) / 2.0
+	} else {
+		0.5
+	};
+	(volume as f64, panning as f64)
+}
+
+fn play_thought_clip(
+	commands: &mut Commands,
+	audio: &Audio,
+	asset_server: &AssetServer,
+	listener: &Transform,
+	thought: &Thought,
+	position: Vec3,
+	source: Option<Entity>,
+) {
+	if let Some(clip) = thought.load_sound(asset_server) {
+		let (volume, panning) = spatial_mix(listener, position);
+		let handle = audio
+		.play(clip)
+		.with_volume(volume)
+		.with_panning(panning)
+		.handle();
+		commands.spawn((ThoughtAudio {handle, position, source}, PrioritySpeaker));
+	}
+}
+
+pub(super) fn play_collected_thought_audio(
+	mut commands: Commands,
+	mut collected: EventReader<ThoughtCollectedEvent>,
+	player: Query<&Transform, With<Player>>,
+	audio: Res<Audio>,
+	asset_server: Res<AssetServer>,
+) {
+	let Ok(listener) = player.get_single() else {
+		return;
+	};
+	for ThoughtCollectedEvent {thought, position, ..} in collected.iter() {
+		play_thought_clip(&mut commands, &audio, &asset_server, listener, thought, *position, None);
+	}
+}
+
+pub(super) fn play_nearby_thought_audio(
+	mut commands: Commands,
+	player: Query<&Transform, With<Player>>,
+	thoughts: Query<(&Thought, &Transform), Without<ThoughtAudioPlayed>>,
+	grid: Res<SpatialGrid>,
+	audio: Res<Audio>,
+	asset_server: Res<AssetServer>,
+) {
+	let Ok(listener) = player.get_single() else {
+		return;
+	};
+	for entry in grid.query_radius(listener.translation, HEARING_RADIUS) {
+		if let Ok((thought, transform)) = thoughts.get(entry.entity) {
+			if thought.audio.is_some() {
+				play_thought_clip(&mut commands, &audio, &asset_server, listener, thought, transform.translation, Some(entry.entity));
+				commands.entity(entry.entity).insert(ThoughtAudioPlayed);
+			}
+		}
+	}
+}
+
+// Thoughts get recycled, a reused one should get to play its clip again
+pub(super) fn forget_played_thoughts(
+	mut commands: Commands,
+	mut removed: RemovedComponents<Thought>,
+	played: Query<(), With<ThoughtAudioPlayed>>,
+) {
+	for entity in removed.iter() {
+		if played.contains(entity) {
+			commands.entity(entity).remove::<ThoughtAudioPlayed>();
+		}
+	}
+}
+
+pub(super) fn update_thought_audio(
+	mut clips: Query<&mut ThoughtAudio>,
+	sources: Query<&Transform, With<Thought>>,
+	player: Query<&Transform, With<Player>>,
+	mut audio_instances: ResMut<Assets<AudioInstance>>,
+) {
+	let Ok(listener) = player.get_single() else {
+		return;
+	};
+	for mut clip in clips.iter_mut() {
+		if let Some(transform) = clip.source.and_then(|source| sources.get(source).ok()) {
+			clip.position = transform.translation;
+		}
+		if let Some(audio_instance) = audio_instances.get_mut(&clip.handle) {
+			let (volume, panning) = spatial_mix(listener, clip.position);
+			audio_instance.set_volume(volume, AudioTween::default());
+			audio_instance.set_panning(panning, AudioTween::default());
+		}
+	}
+}
+
+pub(super) fn clean_up_thought_audio(
+	mut commands: Commands,
+	clips: Query<(Entity, &ThoughtAudio)>,
+	audio_instances: Res<Assets<AudioInstance>>,
+) {
+	for (e, clip) in clips.iter() {
+		if let Some(audio_instance) = audio_instances.get(&clip.handle) {
+			if audio_instance.state() == PlaybackState::Stopped {
+				commands.entity(e).despawn();
+			}
+		}
+	}
+}
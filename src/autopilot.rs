@@ -0,0 +1,140 @@
+use bevy::{prelude::*, ecs::system::SystemParam, utils::HashMap};
+
+use crate::{prelude::*, input::{ActionState, Action, update_action_state}, physics::SpatialGrid, replay::is_replaying, thoughts::Emotion, cabin::LooseCabinThought, GameState};
+
+// Flies the ship when nobody has touched the controls in a while
+#[derive(Resource, Debug, Default)]
+pub struct Autopilot {
+	pub engaged: bool,
+	idle: f32,
+	// Merge was held last frame, let go so the next press counts again
+	merge_held: bool,
+}
+
+pub struct AutopilotPlugin;
+
+impl Plugin for AutopilotPlugin {
+	fn build(&self, app: &mut App) {
+		// `--autopilot` takes over straight away, for leaving the game running on its own
+		let idle_seconds = if std::env::args().any(|arg| arg == "--autopilot") {0.0} else {60.0};
+
+		app
+		.insert_resource(AutopilotParameters {
+			idle_seconds,
+			search_radius: 80.0,
+			cruise_speed: 40.0,
+			merge_at: 3,
+		})
+		.init_resource::<Autopilot>()
+		// A recording already has whatever the autopilot did in it
+		.add_systems((
+			track_idle.run_if(not(is_replaying)),
+			fly_autopilot.run_if(not(is_replaying)),
+		).chain()
+			.in_base_set(CoreSet::PreUpdate)
+			.after(update_action_state)
+			.distributive_run_if(in_state(GameState::Game)))
+		;
+	}
+}
+
+// Any real input hands control back and starts the wait over
+fn track_idle(
+	mut autopilot: ResMut<Autopilot>,
+	parameters: Res<AutopilotParameters>,
+	actions: Res<ActionState>,
+	keyboard: Res<Input<KeyCode>>,
+	mouse: Res<Input<MouseButton>>,
+	gamepad_buttons: Res<Input<GamepadButton>>,
+	time: Res<Time>,
+) {
+	let touched = !actions.values().is_empty()
+		|| !actions.new_motion().is_empty()
+		|| keyboard.get_pressed().len() > 0
+		|| mouse.get_pressed().len() > 0
+		|| gamepad_buttons.get_pressed().len() > 0;
+
+	if touched {
+		if autopilot.engaged {
+			info!("Autopilot disengaged");
+		}
+		autopilot.engaged = false;
+		autopilot.idle = 0.0;
+		return;
+	}
+
+	autopilot.idle += time.delta_seconds();
+	if !autopilot.engaged && autopilot.idle >= parameters.idle_seconds {
+		info!("Autopilot engaged");
+		autopilot.engaged = true;
+	}
+}
+
+// Whether the cabin is in a state where merging does something
+#[derive(SystemParam)]
+struct CabinReadiness<'w, 's> {
+	thoughts: Query<'w, 's, (), LooseCabinThought>,
+	directors: Query<'w, 's, (), With<CabinCutsceneDirector>>,
+}
+
+impl CabinReadiness<'_, '_> {
+	fn can_merge(&self, merge_at: usize) -> bool {
+		self.directors.is_empty() && self.thoughts.iter().count() >= merge_at
+	}
+}
+
+// Heads for the closest positive thought (any thought if there are none) and merges whenever it can
+fn fly_autopilot(
+	mut autopilot: ResMut<Autopilot>,
+	parameters: Res<AutopilotParameters>,
+	mut actions: ResMut<ActionState>,
+	player: Query<(&Transform, &Velocity, &AngularVelocity), With<Player>>,
+	thoughts: Query<(&Transform, &Thought), Without<Player>>,
+	cabin: CabinReadiness,
+	grid: Res<SpatialGrid>,
+) {
+	if !autopilot.engaged {
+		return;
+	}
+	let Ok((transform, velocity, angular)) = player.get_single() else {
+		return;
+	};
+
+	let mut values = HashMap::default();
+
+	let target = grid.query_radius(transform.translation, parameters.search_radius)
+		.filter_map(|entry| thoughts.get(entry.entity).ok())
+		.map(|(thought_transform, thought)| {
+			let distance = thought_transform.translation.distance(transform.translation);
+			// Negative thoughts only get picked when nothing else is around
+			let penalty = if thought.emotion == Emotion::Positive {0.0} else {parameters.search_radius};
+			(thought_transform.translation, distance + penalty)
+		})
+		.min_by(|a, b| a.1.total_cmp(&b.1))
+		.map(|(position, _)| position);
+
+	// Steer in the ship's own frame, damped by how fast it's already turning
+	let inverse = transform.rotation.inverse();
+	let spin = inverse * angular.0;
+	let direction = target
+		.and_then(|target| (inverse * (target - transform.translation)).try_normalize())
+		.unwrap_or(Vec3::NEG_Z);
+	values.insert(Action::Yaw, (-2.0 * direction.x - spin.y).clamp(-1.0, 1.0));
+	values.insert(Action::Pitch, (2.0 * direction.y - spin.x).clamp(-1.0, 1.0));
+	// Rolling pushes along forward, which is -Z
+	values.insert(Action::Roll, spin.z.clamp(-1.0, 1.0));
+	// Only push on when roughly facing the target and not going too fast already
+	let facing = (-direction.z).max(0.0);
+	if velocity.length() < parameters.cruise_speed {
+		values.insert(Action::Thrust, facing * facing);
+	}
+
+	let can_merge = cabin.can_merge(parameters.merge_at);
+	if can_merge && !autopilot.merge_held {
+		values.insert(Action::Merge, 1.0);
+	}
+	autopilot.merge_held = can_merge && !autopilot.merge_held;
+
+	values.retain(|_, value| *value != 0.0);
+	actions.overwrite(&values);
+}
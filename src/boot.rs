@@ -1,18 +1,24 @@
 use bevy::{prelude::*, render::camera::ScalingMode, asset::LoadState};
 
-use crate::GameState;
+use crate::{input::{ActionState, Action}, resources::SafetySettings, GameState};
 
 pub struct BootPlugin;
 
 impl Plugin for BootPlugin {
 	fn build(&self, app: &mut App) {
 		app
+		.insert_resource(SafetySettings {
+			safe_mode: std::env::args().any(|arg| arg == "--safe-mode"),
+			max_feedback_alpha: 0.9,
+			max_colour_change: 4.0,
+		})
 		.add_systems((
 			initial_setup,
 		).in_schedule(OnEnter(GameState::Boot)))
 		.add_systems((
-			advance_slides.run_if(in_state(GameState::Boot)),
-		))
+			advance_slides,
+			toggle_safe_mode,
+		).distributive_run_if(in_state(GameState::Boot)))
 		.add_systems((
 			despawn_entities_with::<Boot>,
 		).in_schedule(OnExit(GameState::Boot)))
@@ -29,9 +35,17 @@ struct Disclaimer;
 #[derive(Component)]
 struct Lore;
 
+#[derive(Component)]
+struct SafeModeText;
+
+fn safe_mode_label(safety: &SafetySettings) -> String {
+	format!("[H] Photosensitivity safe mode: {}", if safety.safe_mode {"ON"} else {"OFF"})
+}
+
 fn initial_setup(
 	mut commands: Commands,
 	asset_server: Res<AssetServer>,
+	safety: Res<SafetySettings>,
 ) {
 	commands
 		.spawn((
@@ -54,7 +68,22 @@ fn initial_setup(
 		Boot,
 		Disclaimer,
 		Name::new("Disclaimer"),
-	));
+	))
+	.with_children(|parent| {
+		parent.spawn((
+			Text2dBundle {
+				text: Text::from_section(safe_mode_label(&safety), TextStyle {
+					font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+					font_size: 40.0,
+					color: Color::WHITE,
+				}).with_alignment(TextAlignment::Center),
+				transform: Transform::from_translation(Vec3::new(0.0, -4.0, 1.0)).with_scale(Vec3::splat(0.01)),
+				..Default::default()
+			},
+			SafeModeText,
+			Name::new("Safe Mode Text"),
+		));
+	});
 
 	commands.spawn((
 		SpriteBundle {
@@ -72,15 +101,14 @@ fn initial_setup(
 fn advance_slides(
 	mut commands: Commands,
 	asset_server: Res<AssetServer>,
-//	mouse: Res<Input<MouseButton>>,
-	keyboard: Res<Input<KeyCode>>,
+	actions: Res<ActionState>,
 	disclaimer_query: Query<(Entity, &Handle<Image>), With<Disclaimer>>,
 	lore_query: Query<Entity, (With<Lore>, Without<Disclaimer>)>,
 	time: Res<Time>,
 	mut next_game_state: ResMut<NextState<GameState>>,
 ) {
 	let mut flag = true;
-	if keyboard.just_pressed(KeyCode::C) {
+	if actions.just_pressed(Action::Advance) {
 		for (disclaimer, texture) in disclaimer_query.iter() {
 			// Check that the image actually loaded before progressing
 			if asset_server.get_load_state(texture) == LoadState::Loaded && time.elapsed_seconds() > 10.0 {
@@ -99,6 +127,26 @@ fn advance_slides(
 	}
 }
 
+// Only while the disclaimer is still up, it's the one telling people about the risk
+fn toggle_safe_mode(
+	actions: Res<ActionState>,
+	mut safety: ResMut<SafetySettings>,
+	mut text_query: Query<&mut Text, With<SafeModeText>>,
+) {
+	if text_query.is_empty() {
+		return;
+	}
+	if actions.just_pressed(Action::ToggleSafeMode) {
+		safety.safe_mode = !safety.safe_mode;
+		info!("Photosensitivity safe mode {}", if safety.safe_mode {"on"} else {"off"});
+	}
+	if safety.is_changed() {
+		for mut text in text_query.iter_mut() {
+			text.sections[0].value = safe_mode_label(&safety);
+		}
+	}
+}
+
 fn despawn_entities_with<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
 	for entity in &to_despawn {
 		commands.entity(entity).despawn_recursive();
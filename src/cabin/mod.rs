@@ -1,12 +1,16 @@
 use bevy::{prelude::*, render::{view::RenderLayers, camera::{RenderTarget, ScalingMode}}, core_pipeline::clear_color::ClearColorConfig, math::Vec3Swizzles};
 
-use crate::{prelude::*, GameState};
+use crate::{prelude::*, replay::is_replaying, GameState};
 
 mod ui;
+mod radar;
 
 pub const CABIN_WIDTH: f32 = 16.0;
 pub const CABIN_HEIGHT: f32 = 9.0;
 
+// Thoughts sitting in the cabin that aren't busy being animated somewhere
+pub type LooseCabinThought = (With<CabinThought>, Without<crate::animation::AnimatedObject>);
+
 pub struct CabinPlugin;
 
 impl Plugin for CabinPlugin {
@@ -16,23 +20,28 @@ impl Plugin for CabinPlugin {
 		.init_resource::<ProgressBar>()
 		.add_event::<ButtonPressEvent>()
 		.add_event::<ThoughtCutsceneEndEvent>()
+		.add_event::<HazardHitEvent>()
 		.add_systems((
 			spawn_cabin_camera,
 			ui::spawn_ui,
 			ui::spawn_bar,
 			ui::spawn_score_counter,
+			radar::spawn_radar,
 		).in_schedule(OnEnter(GameState::Game)))
 		.add_systems((
-			update_cursor_position,
+			update_cursor_position.run_if(not(is_replaying)),
 			ui::track_cursor,
 			spawn_collected_thoughts,
 			move_cabin_thoughts,
 			ui::check_buttons,
+			knock_thoughts_loose,
 		).chain().distributive_run_if(in_state(GameState::Game)))
 		.add_systems((
 			start_thought_animation.before(crate::animation::AnimationSystemSet),
 			check_cutscene_end.after(crate::animation::AnimationSystemSet),
 			ui::update_progress_bar,
+			ui::update_fuel_bar,
+			radar::update_radar.after(crate::physics::InteractionSystemSet),
 			ui::update_score_text,
 			//ui::spawn_win_screen,
 			//ui::despawn_win_screen.after(ui::spawn_win_screen),
@@ -67,11 +76,13 @@ fn spawn_collected_thoughts(
 	assets: Res<AssetServer>,
 	mut collected_thoughts: EventReader<ThoughtCollectedEvent>,
 	mut progress_bar: ResMut<ProgressBar>,
+	scaling: Res<IntensityScaling>,
 ) {
-	for ThoughtCollectedEvent {player: _, thought} in collected_thoughts.iter() {
+	for ThoughtCollectedEvent {thought, ..} in collected_thoughts.iter() {
+		let progress = scaling.progress.at(thought.intensity);
 		match thought.emotion {
-			crate::thoughts::Emotion::Positive => progress_bar.good_progress += 0.1,
-			crate::thoughts::Emotion::Negative => progress_bar.bad_progress += 0.1,
+			crate::thoughts::Emotion::Positive => progress_bar.good_progress += progress,
+			crate::thoughts::Emotion::Negative => progress_bar.bad_progress += progress,
 		}
 		spawn_cabin_thought(&mut commands, &assets, thought.clone());
 	}
@@ -97,6 +108,28 @@ fn spawn_cabin_thought(
 	));
 }
 
+// A hit throws some collected thoughts out of the cabin and rattles the rest
+fn knock_thoughts_loose(
+	mut commands: Commands,
+	mut hits: EventReader<HazardHitEvent>,
+	mut cabin_thoughts: Query<(Entity, &mut Velocity), LooseCabinThought>,
+	spawn: Res<HazardSpawnParameters>,
+	mut rng: ResMut<GameRng>,
+) {
+	use rand::{Rng, seq::IteratorRandom};
+
+	let rng = rng.stream(RngStream::Hazards);
+	for _ in hits.iter() {
+		let lost = cabin_thoughts.iter().map(|(entity, _)| entity).choose_multiple(rng, spawn.knock_loose);
+		for entity in lost.iter() {
+			commands.entity(*entity).despawn_recursive();
+		}
+		for (_, mut velocity) in cabin_thoughts.iter_mut().filter(|(entity, _)| !lost.contains(entity)) {
+			velocity.0 += Vec3::new(rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0), 0.0);
+		}
+	}
+}
+
 fn move_cabin_thoughts(
 	mut cabin_thought_query: Query<(&mut Transform, &mut Velocity), With<CabinThought>>,
 	angular_velocity_query: Query<&AngularVelocity, With<Player>>,
@@ -150,9 +183,11 @@ fn start_thought_animation(
 	asset_server: Res<AssetServer>,
 	mut start_event: EventReader<ButtonPressEvent>,
 	other_director: Query<Entity, With<CabinCutsceneDirector>>,
-	thought_query: Query<Entity, (With<CabinThought>, Without<crate::animation::AnimatedObject>)>,
+	thought_query: Query<(Entity, &CabinThought), Without<crate::animation::AnimatedObject>>,
+	mut rng: ResMut<GameRng>,
 ) {
 	use crate::animation::*;
+	use crate::thoughts::grammar::merge_thoughts;
 	use rand::seq::SliceRandom;
 
 	// If a director is present, break
@@ -170,7 +205,7 @@ fn start_thought_animation(
 		return;
 	}
 
-	let mut rng = rand::thread_rng();
+	let rng = rng.stream(RngStream::Choreography);
 
 	let choreo = [
 	Choreography {
@@ -256,23 +291,20 @@ fn start_thought_animation(
 			(30.0, ChoreographyEvent::EndChoreography)
 		]
 	}
-	].choose(&mut rng).unwrap().clone();
+	].choose(rng).unwrap().clone();
 
-	// Look for actors
-	let mut actors = Vec::new();
-	for actor in thought_query.iter() {
-		if actors.len() >= choreo.n_actors - 2 {
-			break;
-		}
-		actors.push(actor);
-	}
-
-	if actors.len() < choreo.n_actors - 2 {
+	// Look for actors that make up a sentence
+	let (entities, candidates): (Vec<Entity>, Vec<Thought>) = thought_query.iter()
+		.map(|(entity, CabinThought(thought))| (entity, thought.clone()))
+		.unzip();
+	let Some(merge) = merge_thoughts(&candidates, choreo.n_actors - 2) else {
 		// Didnt find enough actors
 		return;
-	}
-	
-	let (left_curtain, right_curtain) = ui::spawn_curtains(&mut commands, asset_server);
+	};
+	let mut actors: Vec<Entity> = merge.order.iter().map(|&i| entities[i]).collect();
+
+	let (left_curtain, right_curtain) = ui::spawn_curtains(&mut commands, &asset_server);
+	ui::spawn_merge_sentence(&mut commands, &asset_server, &merge.sentence);
 
 	actors.insert(0, left_curtain);
 	actors.insert(1, right_curtain);
@@ -282,14 +314,17 @@ fn start_thought_animation(
 	}
 
 	let director = organize_play(&mut commands, choreo, actors);
-	commands.entity(director).insert(CabinCutsceneDirector);//.insert(PrioritySpeaker);
+	commands.entity(director).insert((CabinCutsceneDirector, MergedThought(merge.thought)));//.insert(PrioritySpeaker);
 }
 
 fn update_cursor_position(
 	window_query: Query<&Window>,
 	mut cursor: ResMut<CursorCabinPosition>,
 ) {
-	let window = window_query.get_single().expect("There should be a single window");
+	// Headless runs have no window, the cursor stays wherever it was put
+	let Ok(window) = window_query.get_single() else {
+		return;
+	};
 	if let Some(pos) = window.cursor_position() {
 		cursor.uv_position = Vec2::new(
 			pos.x / window.width(),
@@ -303,13 +338,18 @@ fn update_cursor_position(
 }
 
 fn check_cutscene_end(
+	mut commands: Commands,
 	mut in_event: EventReader<ChoreographyStopEvent>,
 	mut event: EventWriter<ThoughtCutsceneEndEvent>,
-	director: Query<Entity, With<CabinCutsceneDirector>>,
+	director: Query<&MergedThought, With<CabinCutsceneDirector>>,
+	sentences: Query<Entity, With<MergeSentenceText>>,
 ) {
 	for e in in_event.iter() {
-		if let Ok(_) = director.get(e.director) {
-			event.send(ThoughtCutsceneEndEvent);
+		if let Ok(MergedThought(thought)) = director.get(e.director) {
+			event.send(ThoughtCutsceneEndEvent {thought: thought.clone()});
+			for sentence in sentences.iter() {
+				commands.entity(sentence).despawn_recursive();
+			}
 		}
 	}
 }
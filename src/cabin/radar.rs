@@ -0,0 +1,173 @@
+use bevy::{prelude::*, render::view::RenderLayers, sprite::MaterialMesh2dBundle};
+
+use crate::{prelude::*, thoughts::Emotion};
+
+const RADAR_CENTER: Vec3 = Vec3::new(-6.4, 3.0, 20.0);
+const RADAR_RADIUS: f32 = 1.3;
+// The disc is seen at an angle, depth gets squashed by this much
+const RADAR_TILT: f32 = 0.6;
+const MAX_BLIPS: usize = 64;
+
+const POSITIVE_COLOR: Color = Color::rgb(0.3, 1.0, 0.5);
+const NEGATIVE_COLOR: Color = Color::rgb(1.0, 0.3, 0.3);
+
+// Blips get reused from frame to frame, each one is a dot on a stem showing its height above the disc
+#[derive(Component, Default, Debug, Clone)]
+pub struct Radar {
+	blips: Vec<(Entity, Entity)>,
+}
+
+pub fn spawn_radar(
+	mut commands: Commands,
+	mut meshes: ResMut<Assets<Mesh>>,
+	mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+	commands.spawn((MaterialMesh2dBundle {
+		mesh: meshes.add(shape::Circle::new(RADAR_RADIUS).into()).into(),
+		material: materials.add(ColorMaterial::from(Color::rgba(0.0, 0.1, 0.05, 0.6))),
+		transform: Transform::from_translation(RADAR_CENTER).with_scale(Vec3::new(1.0, RADAR_TILT, 1.0)),
+		..Default::default()
+	},
+	RenderLayers::layer(1),
+	Name::new("Radar"),
+	))
+	.with_children(|parent| {
+		// The ship, always in the middle facing up
+		parent.spawn((SpriteBundle {
+			sprite: Sprite {custom_size: Some(Vec2::new(0.08, 0.2)), ..Default::default()},
+			transform: Transform::from_translation(Vec3::new(0.0, 0.0, 1.0)),
+			..Default::default()
+		},
+		RenderLayers::layer(1),
+		));
+	});
+
+	commands.spawn((Radar::default(), Name::new("Radar Blips")));
+}
+
+pub fn update_radar(
+	mut commands: Commands,
+	mut radar: Query<&mut Radar>,
+	mut blips: Query<(&mut Transform, &mut Sprite, &mut Visibility)>,
+	player: Query<&Transform, (With<Player>, Without<Sprite>)>,
+	thoughts: Query<(&Thought, &Transform), Without<Sprite>>,
+	spawn: Res<ThoughtSpawnParameters>,
+) {
+	let (Ok(mut radar), Ok(player)) = (radar.get_single_mut(), player.get_single()) else {
+		return;
+	};
+
+	// Everything that hasn't been despawned yet is in range, which covers most thoughts anyway
+	// so going through them all beats asking the grid for that many cells
+	let range = spawn.despawn_radius;
+	let inverse = player.rotation.inverse();
+	let mut contacts: Vec<(Vec3, Emotion)> = thoughts.iter()
+		.map(|(thought, transform)| (inverse * (transform.translation - player.translation) / range, thought.emotion))
+		.filter(|(local, _)| local.length_squared() <= 1.0)
+		.collect();
+	contacts.sort_by(|a, b| a.0.length_squared().total_cmp(&b.0.length_squared()));
+	contacts.truncate(MAX_BLIPS);
+
+	while radar.blips.len() < contacts.len() {
+		let mut spawn_part = || commands.spawn((SpriteBundle::default(), RenderLayers::layer(1))).id();
+		let blip = (spawn_part(), spawn_part());
+		radar.blips.push(blip);
+	}
+
+	for (i, &(dot, stem)) in radar.blips.iter().enumerate() {
+		let Some(&(local, emotion)) = contacts.get(i) else {
+			for part in [dot, stem] {
+				if let Ok((_, _, mut visibility)) = blips.get_mut(part) {
+					*visibility = Visibility::Hidden;
+				}
+			}
+			continue;
+		};
+		let color = match emotion {
+			Emotion::Positive => POSITIVE_COLOR,
+			Emotion::Negative => NEGATIVE_COLOR,
+		};
+		// Forward is -Z, which goes up on the disc
+		let base = RADAR_CENTER + RADAR_RADIUS * Vec3::new(local.x, -local.z * RADAR_TILT, 1.0);
+		let height = RADAR_RADIUS * local.y * RADAR_TILT;
+
+		if let Ok((mut transform, mut sprite, mut visibility)) = blips.get_mut(dot) {
+			transform.translation = base + Vec3::new(0.0, height, 0.1);
+			sprite.color = color;
+			sprite.custom_size = Some(Vec2::splat(0.08));
+			*visibility = Visibility::Inherited;
+		}
+		if let Ok((mut transform, mut sprite, mut visibility)) = blips.get_mut(stem) {
+			transform.translation = base + Vec3::new(0.0, height / 2.0, 0.0);
+			sprite.color = color.with_a(0.5);
+			sprite.custom_size = Some(Vec2::new(0.02, height.abs()));
+			*visibility = Visibility::Inherited;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::thoughts::ThoughtWord;
+
+	use super::*;
+
+	fn thought(emotion: Emotion) -> Thought {
+		Thought {image: String::new(), audio: None, intensity: 1, emotion, word: ThoughtWord::Noun("radar".into()), behaviour: None}
+	}
+
+	// What the radar shows, nearest first
+	fn shown(app: &mut App) -> Vec<(Vec3, Color)> {
+		let blips = app.world.query::<&Radar>().single(&app.world).blips.clone();
+		blips.iter().filter_map(|(dot, _)| {
+			let dot = app.world.entity(*dot);
+			(dot.get::<Visibility>() == Some(&Visibility::Inherited))
+				.then(|| (dot.get::<Transform>().unwrap().translation, dot.get::<Sprite>().unwrap().color))
+		}).collect()
+	}
+
+	#[test]
+	fn radar_shows_thoughts_in_range_only() {
+		let mut app = App::new();
+		app
+		.insert_resource(ThoughtSpawnParameters {
+			far_radius: 100.0,
+			close_radius: 50.0,
+			total_to_spawn: 0,
+			despawn_radius: 150.0,
+			distribution: SpawnDistribution::Shell,
+			hyperspace_distribution: SpawnDistribution::Shell,
+		})
+		.add_system(update_radar);
+		app.world.spawn((Radar::default(), Name::new("Radar Blips")));
+		// Turned around, so what's behind the ship in the world is in front of it on the disc
+		app.world.spawn((Player, Transform::from_xyz(0.0, 0.0, 1000.0).looking_to(Vec3::Z, Vec3::Y)));
+
+		app.world.spawn((thought(Emotion::Positive), Transform::from_xyz(0.0, 0.0, 1030.0)));
+		let negative = app.world.spawn((thought(Emotion::Negative), Transform::from_xyz(0.0, 60.0, 1000.0))).id();
+		// Past the despawn radius
+		app.world.spawn((thought(Emotion::Positive), Transform::from_xyz(0.0, 0.0, 1151.0)));
+		// Not a thought at all
+		app.world.spawn((Interactable::default(), Transform::from_xyz(0.0, 0.0, 1010.0)));
+
+		// The blips spawn on the first frame and get placed on the second
+		app.update();
+		app.update();
+		let blips = shown(&mut app);
+		assert_eq!(blips.len(), 2, "{:?}", blips);
+
+		let (ahead, color) = blips[0];
+		assert_eq!(color, POSITIVE_COLOR);
+		assert!((ahead.x - RADAR_CENTER.x).abs() < 1e-5, "{}", ahead);
+		assert!((ahead.y - (RADAR_CENTER.y + RADAR_RADIUS * 0.2 * RADAR_TILT)).abs() < 1e-5, "{}", ahead);
+
+		let (above, color) = blips[1];
+		assert_eq!(color, NEGATIVE_COLOR);
+		assert!((above.y - (RADAR_CENTER.y + RADAR_RADIUS * 0.4 * RADAR_TILT)).abs() < 1e-5, "{}", above);
+
+		// Gone thoughts take their blips with them
+		app.world.despawn(negative);
+		app.update();
+		assert_eq!(shown(&mut app).len(), 1);
+	}
+}
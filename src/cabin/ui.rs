@@ -1,6 +1,6 @@
 use bevy::{prelude::*, render::view::RenderLayers, math::Vec3Swizzles};
 
-use crate::prelude::*;
+use crate::{prelude::*, input::{ActionState, Action}};
 
 use super::{CABIN_WIDTH, CABIN_HEIGHT};
 
@@ -64,6 +64,7 @@ pub fn check_buttons(
 	buttons: Query<(Entity, &Transform, &CabinButton)>,
 	cursor: Res<CursorCabinPosition>,
 	mouse: Res<Input<MouseButton>>,
+	actions: Res<ActionState>,
 	mut click_events: EventWriter<ButtonPressEvent>
 ) {
 	if mouse.just_pressed(MouseButton::Left) {
@@ -74,11 +75,16 @@ pub fn check_buttons(
 			}
 		}
 	}
+	if actions.just_pressed(Action::Merge) {
+		for (entity, _, button) in buttons.iter().filter(|(_, _, button)| button.button == ButtonType::MergeThoughts) {
+			click_events.send(ButtonPressEvent {button: entity, button_type: button.button});
+		}
+	}
 }
 
 pub fn spawn_curtains(
 	commands: &mut Commands,
-	asset_server: Res<AssetServer>,
+	asset_server: &AssetServer,
 ) -> (Entity, Entity) {
 	(
 		commands.spawn((SpriteBundle{
@@ -103,6 +109,31 @@ pub fn spawn_curtains(
 	)
 }
 
+// Caption for the sentence the merged thoughts are forming, shown over the curtains
+pub fn spawn_merge_sentence(
+	commands: &mut Commands,
+	asset_server: &AssetServer,
+	sentence: &str,
+) -> Entity {
+	let text_style = TextStyle {
+		font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+		font_size: 60.0,
+		color: Color::rgba(0.9, 0.9, 0.9, 1.0),
+	};
+	commands
+		.spawn((Text2dBundle {
+			text: Text::from_section(sentence, text_style)
+				.with_alignment(TextAlignment::Center),
+			transform: Transform::from_translation(Vec3::new(0.0, -3.6, 110.0))
+				.with_scale(Vec3::splat(0.01)),
+			..Default::default()
+		},
+		MergeSentenceText,
+		RenderLayers::layer(1),
+		Name::new("Merge Sentence"),
+	)).id()
+}
+
 pub fn spawn_score_counter(
 	mut commands: Commands,
 	asset_server: Res<AssetServer>,
@@ -146,6 +177,7 @@ pub fn spawn_score_counter(
 pub fn update_score_text(
 	score_counter: Res<ScoreCounter>,
 	mut score_query: Query<(&mut Transform, &mut Text, &ScoreText)>,
+	safety: Res<SafetySettings>,
 ) {
 	let score = if score_counter.score < 1000000 {
 		format!("{:.4}%", score_counter.score as f32 / 10000.0)
@@ -173,6 +205,14 @@ pub fn update_score_text(
 				0.1,
 				cos.abs());
 		}
+		// Safe mode keeps the wobble but holds the colours still
+		if safety.safe_mode {
+			score_text.sections[0].style.color = if outline.0 {
+				Color::rgba(0.1, 0.1, 0.1, 1.0)
+			} else {
+				Color::rgba(0.9, 0.9, 0.1, 1.0)
+			};
+		}
 	}
 }
 
@@ -252,6 +292,29 @@ pub fn spawn_bar(
 	BadBar,
 	Name::new("Bad Bar"),
 	));
+
+	commands.spawn((SpriteBundle{
+		sprite: Sprite {custom_size: Some(Vec2::new(6.0, 0.5)), ..Default::default()},
+		texture: asset_server.load("ui/bar_back.png"),
+		transform: Transform::from_translation(Vec3::new(6.75, 0.0, 0.0))
+			.with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2)),
+		..Default::default()
+	},
+	RenderLayers::layer(1),
+	Name::new("Fuel Gauge"),
+	));
+
+	commands.spawn((SpriteBundle{
+		sprite: Sprite {custom_size: Some(Vec2::new(5.6, 0.3)), color: Color::rgb(0.4, 0.8, 1.0), ..Default::default()},
+		texture: asset_server.load("ui/good_bar.png"),
+		transform: Transform::from_translation(Vec3::new(6.75, 0.0, 1.0))
+			.with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2)),
+		..Default::default()
+	},
+	RenderLayers::layer(1),
+	FuelBar,
+	Name::new("Fuel Bar"),
+	));
 }
 
 pub fn update_progress_bar(
@@ -264,4 +327,15 @@ pub fn update_progress_bar(
 	
 	good_transform.scale.x = progress_bar.good_progress;
 	bad_transform.scale.x = progress_bar.bad_progress;
+}
+
+pub fn update_fuel_bar(
+	fuel: Res<BoostFuel>,
+	mut fuel_query: Query<(&mut Transform, &mut Sprite), With<FuelBar>>,
+) {
+	for (mut transform, mut sprite) in fuel_query.iter_mut() {
+		transform.scale.x = fuel.fuel;
+		// Dimmed while cooling down
+		sprite.color.set_a(if fuel.cooldown.finished() {1.0} else {0.4});
+	}
 }
\ No newline at end of file
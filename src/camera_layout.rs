@@ -0,0 +1,224 @@
+use bevy::{prelude::*, core_pipeline::clear_color::ClearColorConfig, math::Rect, render::camera::Viewport, utils::HashMap};
+
+use crate::{prelude::*, input::{ActionState, Action}, GameState};
+
+// What a player view camera is for, layouts place cameras by this
+#[derive(Component, Eq, PartialEq, Hash, Debug, Clone, Copy)]
+pub enum CameraRole {
+	Chase,
+	Front,
+	Top,
+	Side,
+}
+
+// Where each role goes, as fractions of the main render texture measured from the top left
+// Listed in drawing order, later views end up on top of earlier ones
+#[derive(Debug, Clone, Copy)]
+pub struct CameraLayout {
+	pub name: &'static str,
+	pub views: &'static [(CameraRole, Rect)],
+}
+
+const fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> Rect {
+	Rect {min: Vec2::new(x0, y0), max: Vec2::new(x1, y1)}
+}
+
+pub const LAYOUTS: [CameraLayout; 4] = [
+	CameraLayout {name: "Grid", views: &[
+		(CameraRole::Side, rect(0.0, 0.5, 0.5, 1.0)),
+		(CameraRole::Top, rect(0.5, 0.5, 1.0, 1.0)),
+		(CameraRole::Front, rect(0.5, 0.0, 1.0, 0.5)),
+		(CameraRole::Chase, rect(0.0, 0.0, 0.5, 0.5)),
+	]},
+	CameraLayout {name: "Single", views: &[
+		(CameraRole::Chase, rect(0.0, 0.0, 1.0, 1.0)),
+	]},
+	CameraLayout {name: "Picture in picture", views: &[
+		(CameraRole::Chase, rect(0.0, 0.0, 1.0, 1.0)),
+		(CameraRole::Front, rect(0.7, 0.05, 0.95, 0.3)),
+	]},
+	CameraLayout {name: "Letterbox", views: &[
+		(CameraRole::Chase, rect(0.0, 0.125, 1.0, 0.875)),
+	]},
+];
+
+const TRANSITION_SECONDS: f32 = 0.6;
+
+#[derive(Resource, Debug)]
+pub struct CameraLayoutState {
+	pub layout: usize,
+	// Where the views were when the current transition started
+	from: HashMap<CameraRole, Rect>,
+	// Where the views are right now
+	shown: HashMap<CameraRole, Rect>,
+	progress: f32,
+	// The texture got wiped last frame and needs to stop being wiped
+	cleared: bool,
+}
+
+impl Default for CameraLayoutState {
+	// Starts settled on the grid instead of growing into it
+	fn default() -> Self {
+		CameraLayoutState {layout: 0, from: HashMap::default(), shown: HashMap::default(), progress: 1.0, cleared: false}
+	}
+}
+
+impl CameraLayoutState {
+	pub fn layout(&self) -> &'static CameraLayout {
+		&LAYOUTS[self.layout]
+	}
+
+	fn target(&self, role: CameraRole) -> Option<Rect> {
+		self.layout().views.iter().find(|(view, _)| *view == role).map(|(_, rect)| *rect)
+	}
+}
+
+pub struct CameraLayoutPlugin;
+
+impl Plugin for CameraLayoutPlugin {
+	fn build(&self, app: &mut App) {
+		app
+		.init_resource::<CameraLayoutState>()
+		.add_systems((
+			cycle_layout,
+			apply_layout,
+		).chain().distributive_run_if(in_state(GameState::Game)))
+		;
+	}
+}
+
+fn cycle_layout(actions: Res<ActionState>, mut state: ResMut<CameraLayoutState>) {
+	if !actions.just_pressed(Action::CycleCameraLayout) {
+		return;
+	}
+	state.layout = (state.layout + 1) % LAYOUTS.len();
+	state.from = state.shown.clone();
+	state.progress = 0.0;
+	info!("Camera layout: {}", state.layout().name);
+}
+
+// Shrinks a view down to nothing around its middle, for views coming in or going away
+fn collapsed(rect: Rect) -> Rect {
+	Rect::from_center_size(rect.center(), Vec2::ZERO)
+}
+
+fn lerp_rect(from: Rect, to: Rect, t: f32) -> Rect {
+	Rect {min: from.min.lerp(to.min, t), max: from.max.lerp(to.max, t)}
+}
+
+fn apply_layout(
+	mut state: ResMut<CameraLayoutState>,
+	render_target: Res<MainRenderTexture>,
+	actions: Res<ActionState>,
+	mut cameras: Query<(&mut Camera, &CameraRole)>,
+	mut clear_camera: Query<&mut Camera3d, With<ClearCamera>>,
+	safety: Res<SafetySettings>,
+	time: Res<Time>,
+) {
+	if state.cleared {
+		state.cleared = false;
+		if actions.value(Action::ClearScreen) == 0.0 {
+			if let Ok(mut camera) = clear_camera.get_single_mut() {
+				camera.clear_color = ClearColorConfig::None;
+			}
+		}
+	}
+
+	let animating = state.progress < 1.0;
+	if !animating && !render_target.is_changed() && state.shown.len() == state.layout().views.len() {
+		return;
+	}
+
+	state.progress = (state.progress + time.delta_seconds() / TRANSITION_SECONDS).min(1.0);
+	// Smoothstep, so views ease in and out of place
+	let t = state.progress * state.progress * (3.0 - 2.0 * state.progress);
+	let size = render_target.size().as_vec2();
+
+	for (mut camera, role) in cameras.iter_mut() {
+		let target = state.target(*role);
+		let from = state.from.get(role).copied();
+		let rect = match (from, target) {
+			(Some(from), Some(to)) => lerp_rect(from, to, t),
+			(None, Some(to)) => lerp_rect(collapsed(to), to, t),
+			(Some(from), None) if state.progress < 1.0 => lerp_rect(from, collapsed(from), t),
+			_ => {
+				state.shown.remove(role);
+				camera.is_active = false;
+				continue;
+			}
+		};
+		state.shown.insert(*role, rect);
+
+		let position = (rect.min * size).round().as_uvec2().min(render_target.size() - UVec2::ONE);
+		let extent = (rect.size() * size).round().as_uvec2().max(UVec2::ONE).min(render_target.size() - position);
+		camera.is_active = true;
+		camera.viewport = Some(Viewport {physical_position: position, physical_size: extent, ..Default::default()});
+		// Views on their way out go underneath everything else
+		let views = state.layout().views;
+		camera.order = match views.iter().position(|(view, _)| view == role) {
+			Some(index) => index as isize - views.len() as isize,
+			None => -10 - *role as isize,
+		};
+	}
+
+	// Wipe what the old layout left behind once the new one has settled
+	// Safe mode lives with the leftovers, the whole picture going dark in one frame is a flash of its own
+	if animating && state.progress >= 1.0 {
+		state.from = state.shown.clone();
+		if safety.safe_mode {
+			return;
+		}
+		if let Ok(mut camera) = clear_camera.get_single_mut() {
+			camera.clear_color = ClearColorConfig::default();
+			state.cleared = true;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+
+	use bevy::time::TimeUpdateStrategy;
+
+	use super::*;
+
+	// Switches to the letterbox and runs until the transition is well past settling
+	// Hands back what the clear camera was doing after every frame
+	fn settle(safe_mode: bool) -> Vec<ClearColorConfig> {
+		let mut app = App::new();
+		app
+		.add_plugins(MinimalPlugins)
+		.init_resource::<ActionState>()
+		.insert_resource(MainRenderTexture {width: 1280, height: 720, texture: Handle::default()})
+		.insert_resource(SafetySettings {safe_mode, max_feedback_alpha: 0.5, max_colour_change: 4.0})
+		.insert_resource(CameraLayoutState {layout: 3, progress: 0.0, ..Default::default()})
+		.add_system(apply_layout);
+
+		for role in [CameraRole::Chase, CameraRole::Front, CameraRole::Top, CameraRole::Side] {
+			app.world.spawn((Camera::default(), role));
+		}
+		let clear = app.world.spawn((Camera3d {clear_color: ClearColorConfig::None, ..Default::default()}, ClearCamera(false))).id();
+
+		let start = Instant::now();
+		(0..=2 * (TRANSITION_SECONDS * 10.0) as u64).map(|frame| {
+			app.insert_resource(TimeUpdateStrategy::ManualInstant(start + Duration::from_millis(100 * frame)));
+			app.update();
+			app.world.get::<Camera3d>(clear).unwrap().clear_color.clone()
+		}).collect()
+	}
+
+	fn wiped(frames: &[ClearColorConfig]) -> usize {
+		frames.iter().filter(|clear| !matches!(clear, ClearColorConfig::None)).count()
+	}
+
+	#[test]
+	fn settling_wipes_for_one_frame() {
+		assert_eq!(wiped(&settle(false)), 1);
+	}
+
+	#[test]
+	fn safe_mode_never_wipes() {
+		assert_eq!(wiped(&settle(true)), 0);
+	}
+}
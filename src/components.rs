@@ -1,13 +1,22 @@
 use bevy::prelude::*;
 
 pub use crate::thoughts::Thought;
+use crate::physics::Shape;
 
 #[derive(Component, Default, Eq, PartialEq, Debug, Clone, Copy)]
 pub struct Player;
 
+// What the player bumps into, centered on the Transform and turned with it
+#[derive(Component, Default, PartialEq, Debug, Clone, Copy)]
+pub struct Hull {
+	pub shape: Shape,
+	// From the Transform to the middle of the shape, in local space
+	pub offset: Vec3,
+}
+
 #[derive(Component, Default, PartialEq, Debug, Clone, Copy)]
 pub struct Interactable {
-	pub radius: f32
+	pub shape: Shape
 }
 
 #[derive(Component, Default, PartialEq, Deref, DerefMut, Debug, Clone, Copy)]
@@ -34,6 +43,9 @@ pub struct GoodBar;
 #[derive(Component, Default, Eq, PartialEq, Debug, Clone, Copy)]
 pub struct BadBar;
 
+#[derive(Component, Default, Eq, PartialEq, Debug, Clone, Copy)]
+pub struct FuelBar;
+
 #[derive(Component, Default, Eq, PartialEq, Debug, Clone, Copy)]
 pub struct CabinCutsceneDirector;
 
@@ -43,6 +55,13 @@ pub struct CabinCamera;
 #[derive(Component, Eq, PartialEq, Debug, Clone)]
 pub struct CabinThought(pub Thought);
 
+// The result of the merge a cutscene director is playing out
+#[derive(Component, Eq, PartialEq, Debug, Clone)]
+pub struct MergedThought(pub Thought);
+
+#[derive(Component, Default, Eq, PartialEq, Debug, Clone, Copy)]
+pub struct MergeSentenceText;
+
 #[derive(Component, PartialEq, Debug, Clone, Copy)]
 pub struct CabinButton {
 	pub button: crate::prelude::ButtonType,
@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 
-use crate::{prelude::*, thoughts::data::ThoughtLibrary, GameState, physics::PhysicsSystemSet};
+use crate::{prelude::*, thoughts::data::ThoughtLibrary, GameState, physics::InteractionSystemSet};
 pub struct DebugPlugin;
 
 impl Plugin for DebugPlugin {
@@ -13,25 +13,29 @@ impl Plugin for DebugPlugin {
 				give_random_thought,
 				debug_buttons,
 				debug_choreography_stops,
+				debug_merges,
 				adjust_progress,
 			).distributive_run_if(in_state(GameState::Game)))
 			.add_system(set_game_state)
-			.add_system(player_interaction.after(PhysicsSystemSet).run_if(in_state(GameState::Game)))
+			.add_system(player_interaction.after(InteractionSystemSet).run_if(in_state(GameState::Game)))
 		;
 	}
 }
 
 fn give_random_thought(
 	keyboard: Res<Input<KeyCode>>,
-	player: Query<Entity, With<Player>>,
+	player: Query<(Entity, &Transform), With<Player>>,
 	thoughts: Res<ThoughtLibrary>,
-	mut event: EventWriter<ThoughtCollectedEvent>
+	mut event: EventWriter<ThoughtCollectedEvent>,
+	mut rng: ResMut<GameRng>,
 ) {
-	if keyboard.just_pressed(KeyCode::G) {
-		let mut rng = rand::thread_rng();
+	if keyboard.just_pressed(KeyCode::G) && !thoughts.is_empty() {
+		let rng = rng.stream(RngStream::Debug);
+		let (player, transform) = player.single();
 		event.send(ThoughtCollectedEvent {
-		    player: player.single(),
-		    thought: thoughts.get_thought_by_index(rand::Rng::gen::<usize>(&mut rng) % thoughts.n_thoughts()),
+		    player,
+		    thought: thoughts.get_thought_by_index(rand::Rng::gen::<usize>(rng) % thoughts.n_thoughts()),
+		    position: transform.translation,
 		});
 	}
 }
@@ -52,6 +56,14 @@ pub fn debug_choreography_stops(
 	}
 }
 
+pub fn debug_merges(
+	mut events: EventReader<ThoughtCutsceneEndEvent>
+) {
+	for e in events.iter() {
+		println!("Thoughts merged into {:?} ({:?}, intensity {})", e.thought.word, e.thought.emotion, e.thought.intensity);
+	}
+}
+
 fn adjust_progress(
 	mut progress_bar: ResMut<ProgressBar>,
 	keyboard: Res<Input<KeyCode>>,
@@ -92,7 +104,11 @@ pub fn set_game_state(
 fn player_interaction(
 	mut events: EventReader<PlayerInteractionEvent>
 ) {
-	for _e in events.iter() {
-		println!("Player is hitting smth!!!");
+	for e in events.iter() {
+		match e.phase {
+			InteractionPhase::Started => println!("Player started hitting {:?}", e.other),
+			InteractionPhase::Ended => println!("Player stopped hitting {:?}", e.other),
+			InteractionPhase::Ongoing => {},
+		}
 	}
 }
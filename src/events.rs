@@ -4,12 +4,25 @@ use crate::prelude::Thought;
 
 pub struct ThoughtCollectedEvent {
 	pub player: Entity,
-	pub thought: Thought
+	pub thought: Thought,
+	// Where the thought was when it got collected
+	pub position: Vec3,
 }
 
 pub struct PlayerInteractionEvent {
 	pub player: Entity,
-	pub other: Entity
+	pub other: Entity,
+	pub phase: InteractionPhase,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum InteractionPhase {
+	// First frame of the overlap
+	Started,
+	// Every frame after that while they still overlap
+	Ongoing,
+	// They stopped overlapping, or the other one stopped being interactable
+	Ended,
 }
 
 pub struct ChoreographyStopEvent {
@@ -21,7 +34,14 @@ pub struct ButtonPressEvent {
 	pub button_type: ButtonType
 }
 
-pub struct ThoughtCutsceneEndEvent;
+pub struct BoostEvent;
+
+pub struct HazardHitEvent;
+
+pub struct ThoughtCutsceneEndEvent {
+	// The composite thought the merged ones turned into
+	pub thought: Thought
+}
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ButtonType {
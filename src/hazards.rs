@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{prelude::*, physics::{SpatialGrid, Shape, InteractionSystemSet}, thoughts::distribution::{sample_shell, random_direction}, GameState};
+
+// Intrusive thoughts, things to steer clear of
+#[derive(Component, Default, Eq, PartialEq, Debug, Clone, Copy)]
+pub struct Hazard;
+
+// Mesh and material every hazard shares
+#[derive(Resource, Debug, Clone)]
+struct HazardAssets {
+	mesh: Handle<Mesh>,
+	material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for HazardAssets {
+	fn from_world(world: &mut World) -> Self {
+		let spawn = *world.resource::<HazardSpawnParameters>();
+		HazardAssets {
+			mesh: world.resource_mut::<Assets<Mesh>>().add(Mesh::from(shape::Capsule {
+				radius: spawn.radius, depth: 2.0 * spawn.half_length, ..Default::default()
+			})),
+			material: world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial {
+				base_color: Color::rgb(0.1, 0.0, 0.05),
+				emissive: Color::rgb(0.6, 0.0, 0.1),
+				..Default::default()
+			}),
+		}
+	}
+}
+
+pub struct HazardsPlugin;
+
+impl Plugin for HazardsPlugin {
+	fn build(&self, app: &mut App) {
+		app
+		.insert_resource(HazardSpawnParameters {
+			far_radius: 100.0,
+			close_radius: 50.0,
+			despawn_radius: 150.0,
+			total_to_spawn: 40,
+			radius: 1.5,
+			half_length: 3.0,
+			max_tumble: 1.5,
+			push: 60.0,
+			bad_progress: 0.05,
+			knock_loose: 2,
+		})
+		.init_resource::<HazardAssets>()
+		.add_event::<HazardHitEvent>()
+		.add_systems((
+			spawn_hazards,
+			hit_hazards,
+			despawn_hazards,
+		).chain().after(InteractionSystemSet).distributive_run_if(in_state(GameState::Game)))
+		;
+	}
+}
+
+fn spawn_hazards(
+	mut commands: Commands,
+	hazards: Query<(), With<Hazard>>,
+	player: Query<&Transform, With<Player>>,
+	assets: Res<HazardAssets>,
+	spawn: Res<HazardSpawnParameters>,
+	mut rng: ResMut<GameRng>,
+) {
+	let player_position = player.get_single().map_or(Vec3::ZERO, |transform| transform.translation);
+	let rng = rng.stream(RngStream::Hazards);
+
+	let mut already_spawned = hazards.iter().count() as u32;
+	let mut spawning_capacity = 5;
+	while spawn.total_to_spawn > already_spawned && spawning_capacity > 0 {
+		spawning_capacity -= 1;
+		let location = player_position + sample_shell(rng, spawn.close_radius, spawn.far_radius);
+		let rotation = Quat::from_rotation_arc(Vec3::Y, random_direction(rng));
+		let tumble = rng.gen_range(0.2..=1.0) * spawn.max_tumble * random_direction(rng);
+		commands.spawn((
+			PbrBundle {
+				mesh: assets.mesh.clone(),
+				material: assets.material.clone(),
+				transform: Transform::from_translation(location).with_rotation(rotation),
+				..Default::default()
+			},
+			Hazard,
+			Interactable {shape: Shape::Capsule {half_length: spawn.half_length, radius: spawn.radius}},
+			AngularVelocity(tumble),
+			Name::new(format!("Hazard@[{:.1};{:.1};{:.1}]", location.x, location.y, location.z)),
+		));
+		already_spawned += 1;
+	}
+}
+
+// Bounces the ship off and makes the bad bar grow, the cabin takes care of its own part
+fn hit_hazards(
+	mut players: Query<(&Transform, &mut Velocity), With<Player>>,
+	hazards: Query<&Transform, (With<Hazard>, Without<Player>)>,
+	spawn: Res<HazardSpawnParameters>,
+	mut progress_bar: ResMut<ProgressBar>,
+	mut collisions: EventReader<PlayerInteractionEvent>,
+	mut hits: EventWriter<HazardHitEvent>,
+) {
+	for event in collisions.iter().filter(|event| event.phase == InteractionPhase::Started) {
+		let (Ok((player_transform, mut velocity)), Ok(hazard_transform)) = (players.get_mut(event.player), hazards.get(event.other)) else {
+			continue;
+		};
+		let away = (player_transform.translation - hazard_transform.translation).try_normalize().unwrap_or(player_transform.back());
+		// Lose whatever speed was heading into the hazard, then get shoved out
+		let into = velocity.dot(away).min(0.0);
+		velocity.0 += (spawn.push - into) * away;
+		progress_bar.bad_progress += spawn.bad_progress;
+		hits.send(HazardHitEvent);
+	}
+}
+
+fn despawn_hazards(
+	mut commands: Commands,
+	hazards: Query<(), With<Hazard>>,
+	player_transform: Query<&Transform, With<Player>>,
+	grid: Res<SpatialGrid>,
+	spawn: Res<HazardSpawnParameters>,
+) {
+	if let Ok(player_transform) = player_transform.get_single() {
+		for entry in grid.outside_radius(player_transform.translation, spawn.despawn_radius) {
+			if hazards.contains(entry.entity) {
+				commands.entity(entry.entity).despawn_recursive();
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+
+	use bevy::{asset::AssetPlugin, ecs::event::ManualEventReader, time::TimeUpdateStrategy};
+
+	use super::*;
+	use crate::physics::PhysicsPlugin;
+
+	#[test]
+	fn flying_through_a_hazard_hits_once() {
+		let mut app = App::new();
+		app
+		.add_plugins(MinimalPlugins)
+		.add_plugin(AssetPlugin::default())
+		.add_asset::<Mesh>()
+		.add_asset::<StandardMaterial>()
+		.add_state::<GameState>()
+		.add_plugin(PhysicsPlugin)
+		.add_plugin(HazardsPlugin)
+		.init_resource::<ProgressBar>()
+		.insert_resource(GameRng::from_seed(18))
+		.insert_resource(FixedTime::new_from_secs(1.0 / 60.0))
+		.insert_resource(NextState(Some(GameState::Game)));
+		// Only the hazard placed below, and no shove so the two stay overlapped for a while
+		let spawn = HazardSpawnParameters {total_to_spawn: 0, push: 0.0, ..*app.world.resource::<HazardSpawnParameters>()};
+		app.insert_resource(spawn);
+
+		app.world.spawn((
+			Player,
+			Hull {shape: Shape::Sphere {radius: 1.0}, offset: Vec3::ZERO},
+			Transform::default(),
+			Velocity(Vec3::X * 10.0),
+		));
+		let hazard = app.world.spawn((
+			Hazard,
+			Interactable {shape: Shape::Capsule {half_length: spawn.half_length, radius: spawn.radius}},
+			Transform::from_xyz(10.0, 0.0, 0.0),
+			Velocity(Vec3::NEG_X * 10.0),
+		)).id();
+
+		let mut interactions = ManualEventReader::<PlayerInteractionEvent>::default();
+		let mut hit_reader = ManualEventReader::<HazardHitEvent>::default();
+		let (mut phases, mut hits) = (Vec::new(), Vec::new());
+		let start = Instant::now();
+		for frame in 0..120 {
+			app.insert_resource(TimeUpdateStrategy::ManualInstant(start + Duration::from_secs_f64(frame as f64 / 60.0)));
+			app.update();
+			let events = app.world.resource::<Events<PlayerInteractionEvent>>();
+			let frame_phases: Vec<InteractionPhase> = interactions.iter(events).filter(|event| event.other == hazard).map(|event| event.phase).collect();
+			let started = frame_phases.contains(&InteractionPhase::Started);
+			phases.extend(frame_phases);
+			for _ in hit_reader.iter(app.world.resource::<Events<HazardHitEvent>>()) {
+				hits.push(started);
+			}
+		}
+
+		assert!(phases.iter().filter(|phase| **phase == InteractionPhase::Ongoing).count() > 1, "the two should stay in contact for a while: {:?}", phases);
+		assert!(phases.contains(&InteractionPhase::Ended));
+		// One hit, on the frame the contact started
+		assert_eq!(hits, vec![true]);
+		assert!((app.world.resource::<ProgressBar>().bad_progress - spawn.bad_progress).abs() < 1e-6);
+	}
+}
@@ -0,0 +1,133 @@
+use std::fmt::Display;
+
+use bevy::{prelude::*, reflect::TypeUuid, asset::{AssetLoader, LoadContext, LoadedAsset}, utils::{BoxedFuture, HashMap}};
+use serde::Deserialize;
+
+use super::Action;
+
+pub const INPUT_BINDINGS_PATH: &str = "input/default.bindings.ron";
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MouseAxis {
+	X,
+	Y,
+}
+
+// One way of driving an action, an action can have any number of them and they add up
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Binding {
+	Key(KeyCode),
+	// Two keys pulling an axis in opposite directions
+	KeyAxis {negative: KeyCode, positive: KeyCode},
+	Mouse(MouseButton),
+	// Sensitivity is per pixel moved between fixed steps
+	MouseMotion {axis: MouseAxis, sensitivity: f32},
+	GamepadButton(GamepadButtonType),
+	GamepadAxis {axis: GamepadAxisType, #[serde(default)] inverted: bool},
+}
+
+// Doubles as the asset loaded from disk and the resource the actions are read through
+#[derive(Resource, Deserialize, TypeUuid, Clone, Debug)]
+#[uuid = "b7d2e4a1-3c58-4f90-8e6b-2a1f0c9d7e34"]
+pub struct InputBindings {
+	// Gamepad axes closer to rest than this read as 0
+	pub deadzone: f32,
+	bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputBindings {
+	// The controls from before they could be rebound, so the ship still flies until the bindings file loads
+	fn default() -> Self {
+		let bindings = [
+			(Action::Thrust, Binding::KeyAxis {negative: KeyCode::S, positive: KeyCode::W}),
+			(Action::Yaw, Binding::KeyAxis {negative: KeyCode::D, positive: KeyCode::A}),
+			(Action::Roll, Binding::KeyAxis {negative: KeyCode::Q, positive: KeyCode::E}),
+			(Action::ClearScreen, Binding::Key(KeyCode::C)),
+			(Action::Advance, Binding::Key(KeyCode::C)),
+		].into_iter().map(|(action, binding)| (action, vec![binding])).collect();
+		InputBindings {deadzone: 0.1, bindings}
+	}
+}
+
+impl InputBindings {
+	pub fn get(&self, action: Action) -> &[Binding] {
+		self.bindings.get(&action).map(Vec::as_slice).unwrap_or(&[])
+	}
+
+	fn validate(&self) -> Result<(), InputBindingsError> {
+		if !(0.0..1.0).contains(&self.deadzone) {
+			return Err(InputBindingsError::Deadzone(self.deadzone));
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug)]
+pub enum InputBindingsError {
+	Parse(ron::error::SpannedError),
+	Deadzone(f32),
+}
+
+impl Display for InputBindingsError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			InputBindingsError::Parse(error) => write!(f, "malformed input bindings: {}", error),
+			InputBindingsError::Deadzone(deadzone) => write!(f, "deadzone {} is outside [0, 1)", deadzone),
+		}
+	}
+}
+
+impl std::error::Error for InputBindingsError {}
+
+#[derive(Default)]
+pub struct InputBindingsLoader;
+
+impl AssetLoader for InputBindingsLoader {
+	fn load<'a>(
+		&'a self,
+		bytes: &'a [u8],
+		load_context: &'a mut LoadContext,
+	) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+		Box::pin(async move {
+			let bindings = ron::de::from_bytes::<InputBindings>(bytes).map_err(InputBindingsError::Parse)?;
+			bindings.validate()?;
+			load_context.set_default_asset(LoadedAsset::new(bindings));
+			Ok(())
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["bindings.ron"]
+	}
+}
+
+// Keeps the bindings asset alive (and watched for changes)
+#[derive(Resource, Debug, Clone)]
+pub struct InputBindingsHandle(pub Handle<InputBindings>);
+
+pub fn load_input_bindings(mut commands: Commands, asset_server: Res<AssetServer>) {
+	commands.insert_resource(InputBindingsHandle(asset_server.load(INPUT_BINDINGS_PATH)));
+}
+
+// Copies the bindings into the resource whenever they (re)load
+pub fn update_input_bindings(
+	mut commands: Commands,
+	mut events: EventReader<AssetEvent<InputBindings>>,
+	bindings: Res<Assets<InputBindings>>,
+	handle: Option<Res<InputBindingsHandle>>,
+) {
+	let Some(handle) = handle else {
+		return;
+	};
+	for event in events.iter() {
+		match event {
+			AssetEvent::Created {handle: changed} | AssetEvent::Modified {handle: changed} if *changed == handle.0 => {
+				if let Some(loaded) = bindings.get(changed) {
+					info!("Loaded input bindings");
+					commands.insert_resource(loaded.clone());
+				}
+			},
+			_ => {}
+		}
+	}
+}
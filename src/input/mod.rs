@@ -0,0 +1,228 @@
+use bevy::{prelude::*, input::{InputSystem, mouse::MouseMotion}, ecs::system::SystemParam, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+pub mod bindings;
+
+use bindings::*;
+use crate::{replay::is_replaying, physics::PhysicsSystemSet};
+
+// Everything the game can be told to do, whatever device the order comes from
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+	Thrust,
+	Yaw,
+	Roll,
+	Pitch,
+	Boost,
+	Merge,
+	ClearScreen,
+	// Moves the boot slides along
+	Advance,
+	CycleCameraLayout,
+	ToggleSafeMode,
+	// Changes where thoughts spawn around the ship
+	CycleSpawnDistribution,
+}
+
+impl Action {
+	pub const ALL: [Action; 11] = [
+		Action::Thrust, Action::Yaw, Action::Roll, Action::Pitch,
+		Action::Boost, Action::Merge, Action::ClearScreen, Action::Advance,
+		Action::CycleCameraLayout, Action::ToggleSafeMode, Action::CycleSpawnDistribution,
+	];
+}
+
+// Axis values at or beyond this count as the action being held down
+const PRESS_THRESHOLD: f32 = 0.5;
+
+// What the bindings add up to this frame, read this instead of the raw devices
+#[derive(Resource, Default, Debug)]
+pub struct ActionState {
+	values: HashMap<Action, f32>,
+	// Mouse motion waits here until a fixed step uses it, frames and steps don't line up one to one
+	motion: HashMap<Action, f32>,
+	// Motion that came in this frame, kept apart for recordings
+	new_motion: HashMap<Action, f32>,
+	buttons: Input<Action>,
+}
+
+impl ActionState {
+	// In [-1, 1], buttons only ever give 0 or 1
+	pub fn value(&self, action: Action) -> f32 {
+		let value = self.values.get(&action).copied().unwrap_or(0.0);
+		let motion = self.motion.get(&action).copied().unwrap_or(0.0);
+		(value + motion).clamp(-1.0, 1.0)
+	}
+
+	pub fn just_pressed(&self, action: Action) -> bool {
+		self.buttons.just_pressed(action)
+	}
+
+	pub fn just_released(&self, action: Action) -> bool {
+		self.buttons.just_released(action)
+	}
+
+	// Non-zero values only, which is all a recording needs to rebuild the frame
+	pub fn values(&self) -> HashMap<Action, f32> {
+		self.values.iter().filter(|(_, value)| **value != 0.0).map(|(action, value)| (*action, *value)).collect()
+	}
+
+	// Mouse motion that came in this frame, non-zero values only
+	pub fn new_motion(&self) -> HashMap<Action, f32> {
+		self.new_motion.clone()
+	}
+
+	// Stacks onto whatever motion no fixed step has used yet
+	pub fn add_motion(&mut self, motion: HashMap<Action, f32>) {
+		for (action, value) in motion.iter() {
+			*self.motion.entry(*action).or_default() += value;
+		}
+		self.new_motion = motion;
+	}
+
+	fn consume_motion(&mut self) {
+		self.motion.clear();
+	}
+
+	// Takes the frame from somewhere other than the devices, like a recording or the autopilot
+	pub fn overwrite(&mut self, values: &HashMap<Action, f32>) {
+		self.buttons.clear();
+		for action in Action::ALL {
+			self.set(action, values.get(&action).copied().unwrap_or(0.0));
+		}
+	}
+
+	fn set(&mut self, action: Action, value: f32) {
+		let value = value.clamp(-1.0, 1.0);
+		self.values.insert(action, value);
+		if value.abs() >= PRESS_THRESHOLD {
+			self.buttons.press(action);
+		} else {
+			self.buttons.release(action);
+		}
+	}
+}
+
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+	fn build(&self, app: &mut App) {
+		app
+		.add_asset::<InputBindings>()
+		.init_asset_loader::<InputBindingsLoader>()
+		.init_resource::<InputBindings>()
+		.init_resource::<ActionState>()
+		.add_startup_system(load_input_bindings)
+		.add_system(update_input_bindings)
+		.add_system(update_action_state.in_base_set(CoreSet::PreUpdate).after(InputSystem).run_if(not(is_replaying)))
+		.add_system(consume_mouse_motion.after(PhysicsSystemSet).in_schedule(CoreSchedule::FixedUpdate))
+		;
+	}
+}
+
+// Raw state of everything bindings can point at
+#[derive(SystemParam)]
+pub struct InputDevices<'w, 's> {
+	keyboard: Res<'w, Input<KeyCode>>,
+	mouse: Res<'w, Input<MouseButton>>,
+	mouse_motion: EventReader<'w, 's, MouseMotion>,
+	gamepads: Res<'w, Gamepads>,
+	gamepad_buttons: Res<'w, Input<GamepadButton>>,
+	gamepad_button_axes: Res<'w, Axis<GamepadButton>>,
+	gamepad_axes: Res<'w, Axis<GamepadAxis>>,
+}
+
+pub fn update_action_state(
+	mut actions: ResMut<ActionState>,
+	bindings: Res<InputBindings>,
+	devices: InputDevices,
+) {
+	let InputDevices {keyboard, mouse, mut mouse_motion, gamepads, gamepad_buttons, gamepad_button_axes, gamepad_axes} = devices;
+	let motion: Vec2 = mouse_motion.iter().map(|event| event.delta).sum();
+	let deadzone = |value: f32| if value.abs() < bindings.deadzone {0.0} else {value};
+
+	actions.buttons.clear();
+	let mut new_motion = HashMap::default();
+	for action in Action::ALL {
+		let mut moved = 0.0;
+		let value = bindings.get(action).iter().map(|binding| match binding {
+			Binding::Key(key) => keyboard.pressed(*key) as u8 as f32,
+			Binding::KeyAxis {negative, positive} => keyboard.pressed(*positive) as u8 as f32 - keyboard.pressed(*negative) as u8 as f32,
+			Binding::Mouse(button) => mouse.pressed(*button) as u8 as f32,
+			// Adds up separately, see ActionState::motion
+			Binding::MouseMotion {axis, sensitivity} => {
+				moved += match axis {
+					MouseAxis::X => motion.x * sensitivity,
+					// Screen coordinates grow downwards, pushing the mouse away should be positive
+					MouseAxis::Y => -motion.y * sensitivity,
+				};
+				0.0
+			},
+			Binding::GamepadButton(button_type) => gamepads.iter()
+				.map(|gamepad| {
+					let button = GamepadButton::new(gamepad, *button_type);
+					// Triggers are analog, everything else is just down or up
+					gamepad_button_axes.get(button)
+						.map(deadzone)
+						.unwrap_or(gamepad_buttons.pressed(button) as u8 as f32)
+				})
+				.fold(0.0, f32::max),
+			Binding::GamepadAxis {axis, inverted} => {
+				let sign = if *inverted {-1.0} else {1.0};
+				gamepads.iter()
+					.filter_map(|gamepad| gamepad_axes.get(GamepadAxis::new(gamepad, *axis)))
+					.map(|value| sign * deadzone(value))
+					.sum()
+			},
+		}).sum();
+		actions.set(action, value);
+		if moved != 0.0 {
+			new_motion.insert(action, moved);
+		}
+	}
+	actions.add_motion(new_motion);
+}
+
+// Runs at the end of every fixed step, whatever moved the ship this step shouldn't move it again next step
+fn consume_mouse_motion(mut actions: ResMut<ActionState>) {
+	actions.consume_motion();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn mouse_motion_waits_for_a_fixed_step() {
+		let mut actions = ActionState::default();
+		// Two frames go by without a fixed step
+		actions.add_motion([(Action::Yaw, 0.2)].into_iter().collect());
+		actions.add_motion([(Action::Yaw, 0.1)].into_iter().collect());
+		assert!((actions.value(Action::Yaw) - 0.3).abs() < 1e-6);
+		assert_eq!(actions.new_motion().get(&Action::Yaw), Some(&0.1));
+
+		actions.consume_motion();
+		assert_eq!(actions.value(Action::Yaw), 0.0);
+	}
+
+	#[test]
+	fn default_bindings_fly_the_ship() {
+		let bindings = InputBindings::default();
+		for action in [Action::Thrust, Action::Yaw, Action::Roll, Action::ClearScreen, Action::Advance] {
+			assert!(!bindings.get(action).is_empty(), "{:?} is unbound", action);
+		}
+	}
+
+	#[test]
+	fn shipped_bindings_cover_every_action() {
+		let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join(INPUT_BINDINGS_PATH);
+		let bytes = std::fs::read(&path).expect("The bindings file should be there");
+		let bindings = ron::de::from_bytes::<InputBindings>(&bytes).expect("The bindings file should parse");
+		for action in Action::ALL {
+			assert!(!bindings.get(action).is_empty(), "{:?} is unbound", action);
+		}
+		for action in [Action::Yaw, Action::Pitch] {
+			assert!(bindings.get(action).iter().any(|binding| matches!(binding, Binding::MouseMotion {..})), "{:?} has no mouse look", action);
+		}
+	}
+}
@@ -1,4 +1,4 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, app::ScheduleRunnerPlugin, window::ExitCondition, winit::WinitPlugin, render::{RenderPlugin, settings::WgpuSettings}};
 
 mod prelude {
 	pub use crate::resources::*;
@@ -12,14 +12,19 @@ mod test_scene;
 mod resources;
 mod animation;
 mod thoughts;
+mod hazards;
 mod physics;
+mod input;
 mod player;
+mod camera_layout;
 mod events;
 mod score;
 mod cabin;
 mod audio;
 mod boot;
 mod vfx;
+mod replay;
+mod autopilot;
 
 #[cfg(debug_assertions)]
 mod debug;
@@ -32,6 +37,15 @@ pub enum GameState {
 }
 
 fn main() {
+	game_app(replay::headless(), replay::ReplayPlugin::default()).run();
+	// Only a headless run gets back here, a window takes the process down with it
+	if replay::diverged() {
+		std::process::exit(1);
+	}
+}
+
+// The whole game short of running it, tests step it frame by frame instead
+fn game_app(headless: bool, replay: replay::ReplayPlugin) -> App {
 	let default_plugins = DefaultPlugins;
 
 	let default_plugins = default_plugins.set(WindowPlugin {
@@ -41,6 +55,16 @@ fn main() {
 		}), ..Default::default()
 	});
 
+	// Replays can run without a window or GPU, for checking them on machines that have neither
+	let default_plugins = if headless {
+		default_plugins
+			.set(WindowPlugin {primary_window: None, exit_condition: ExitCondition::DontExit, ..Default::default()})
+			.set(RenderPlugin {wgpu_settings: WgpuSettings {backends: None, ..Default::default()}})
+			.disable::<WinitPlugin>()
+	} else {
+		default_plugins
+	};
+
 	#[cfg(debug_assertions)]
 	let default_plugins = default_plugins.set(AssetPlugin {
 		watch_for_changes: true,
@@ -52,7 +76,12 @@ fn main() {
 	app
 		.add_plugins(default_plugins)
 		.add_state::<GameState>()
+		// Up here since more than one plugin hooks into its transitions
+		.add_state::<player::ShipState>()
+		.init_resource::<resources::RenderResolution>()
 		.init_resource::<resources::MainRenderTexture>()
+		.init_resource::<resources::GameRng>()
+		.add_plugin(input::InputPlugin {})
 		.add_plugin(boot::BootPlugin {})
 		.add_plugin(cabin::CabinPlugin {})
 		.add_plugin(audio::AudioPlugin {})
@@ -60,16 +89,26 @@ fn main() {
 		.add_plugin(animation::AnimationPlugin {})
 		.add_plugin(physics::PhysicsPlugin {})
 		.add_plugin(player::PlayerBehaviourPlugin {})
+		.add_plugin(camera_layout::CameraLayoutPlugin {})
 		.add_plugin(thoughts::ThoughtsPlugin {})
+		.add_plugin(hazards::HazardsPlugin {})
 		.add_plugin(test_scene::SetupPlugin {})
-		.add_plugin(vfx::VFXPlugin {})
+		.add_plugin(replay)
+		.add_plugin(autopilot::AutopilotPlugin {})
 		.add_startup_system(player::spawn_player_and_cameras)
 	;
+
+	if headless {
+		app.add_plugin(ScheduleRunnerPlugin);
+	} else {
+		// Effects present to the window, there is nothing to show them on otherwise
+		app.add_plugin(vfx::VFXPlugin {});
+	}
 	
 	{
 		#[cfg(debug_assertions)]
 		app.add_plugin(debug::DebugPlugin);
 	}
-	
-	app.run();
+
+	app
 }
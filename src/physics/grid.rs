@@ -0,0 +1,280 @@
+use bevy::{prelude::*, utils::{HashMap, StableHashMap}};
+
+use crate::prelude::Interactable;
+
+// Uniform grid broadphase over Interactables, answers proximity queries without touching every object
+#[derive(Resource, Debug, Clone)]
+pub struct SpatialGrid {
+	cell_size: f32,
+	// Largest radius in the grid, queries need to look this much further to catch big objects
+	max_radius: f32,
+	// Something as big as max_radius went away, it gets worked out again on the next refresh
+	max_radius_stale: bool,
+	// Stable so despawning walks the cells in the same order every run, replays depend on it
+	cells: StableHashMap<IVec3, Vec<GridEntry>>,
+	locations: HashMap<Entity, IVec3>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridEntry {
+	pub entity: Entity,
+	pub position: Vec3,
+	pub radius: f32,
+}
+
+impl Default for SpatialGrid {
+	fn default() -> Self {
+		Self::new(10.0)
+	}
+}
+
+impl SpatialGrid {
+	pub fn new(cell_size: f32) -> Self {
+		SpatialGrid {
+			cell_size,
+			max_radius: 0.0,
+			max_radius_stale: false,
+			cells: StableHashMap::default(),
+			locations: HashMap::default(),
+		}
+	}
+
+	fn cell_of(&self, position: Vec3) -> IVec3 {
+		(position / self.cell_size).floor().as_ivec3()
+	}
+
+	// Inserts the entity or moves it to its new position
+	pub fn update(&mut self, entity: Entity, position: Vec3, radius: f32) {
+		let cell = self.cell_of(position);
+		let entry = GridEntry {entity, position, radius};
+
+		let old = match self.locations.insert(entity, cell) {
+			Some(old_cell) if old_cell == cell => {
+				if let Some(existing) = self.cells.get_mut(&cell).and_then(|c| c.iter_mut().find(|e| e.entity == entity)) {
+					let old = *existing;
+					*existing = entry;
+					self.resize(old.radius, radius);
+					return;
+				}
+				None
+			},
+			Some(old_cell) => self.remove_from_cell(entity, old_cell),
+			None => None,
+		};
+		self.resize(old.map_or(0.0, |old| old.radius), radius);
+		self.cells.entry(cell).or_default().push(entry);
+	}
+
+	pub fn remove(&mut self, entity: Entity) {
+		if let Some(cell) = self.locations.remove(&entity) {
+			if let Some(old) = self.remove_from_cell(entity, cell) {
+				self.resize(old.radius, 0.0);
+			}
+		}
+	}
+
+	fn remove_from_cell(&mut self, entity: Entity, cell: IVec3) -> Option<GridEntry> {
+		let entries = self.cells.get_mut(&cell)?;
+		let index = entries.iter().position(|e| e.entity == entity)?;
+		let removed = entries.remove(index);
+		if entries.is_empty() {
+			self.cells.remove(&cell);
+		}
+		Some(removed)
+	}
+
+	// Growing is cheap to keep track of, shrinking needs a look over everything so it waits for refresh_max_radius
+	fn resize(&mut self, old_radius: f32, radius: f32) {
+		if radius < old_radius && old_radius >= self.max_radius {
+			self.max_radius_stale = true;
+		}
+		self.max_radius = self.max_radius.max(radius);
+	}
+
+	pub fn refresh_max_radius(&mut self) {
+		if self.max_radius_stale {
+			self.max_radius = self.cells.values().flatten().map(|e| e.radius).fold(0.0, f32::max);
+			self.max_radius_stale = false;
+		}
+	}
+
+	// All entries whose sphere touches the sphere given by center and radius.
+	// Meant for radii of a few cells (swarming, hearing, hull contacts), the box of cells to look at grows with the cube of
+	// (radius + max_radius) / cell_size, so past the number of occupied cells it walks those instead.
+	pub fn query_radius(&self, center: Vec3, radius: f32) -> impl Iterator<Item = &GridEntry> + '_ {
+		let reach = radius + self.max_radius;
+		let min = self.cell_of(center - Vec3::splat(reach));
+		let max = self.cell_of(center + Vec3::splat(reach));
+		let span = |min: i32, max: i32| (max as i64 - min as i64 + 1).max(0);
+		let box_cells = span(min.x, max.x).saturating_mul(span(min.y, max.y)).saturating_mul(span(min.z, max.z));
+		let walk_box = box_cells <= self.cells.len() as i64;
+
+		let in_box = walk_box.then(|| {
+			(min.x..=max.x).flat_map(move |x| (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| IVec3::new(x, y, z))))
+				.filter_map(|cell| self.cells.get(&cell))
+		}).into_iter().flatten();
+		let occupied = (!walk_box).then(|| {
+			self.cells.iter()
+				.filter(move |(cell, _)| cell.cmpge(min).all() && cell.cmple(max).all())
+				.map(|(_, entries)| entries)
+		}).into_iter().flatten();
+
+		in_box.chain(occupied)
+			.flatten()
+			.filter(move |e| {
+				let distance = radius + e.radius;
+				(e.position - center).length_squared() <= distance * distance
+			})
+	}
+
+	// All entries whose center lies further than radius from the given point, whole cells get skipped or taken at once
+	pub fn outside_radius(&self, center: Vec3, radius: f32) -> impl Iterator<Item = &GridEntry> + '_ {
+		let radius_squared = radius * radius;
+		self.cells.iter().flat_map(move |(cell, entries)| {
+			let cell_min = cell.as_vec3() * self.cell_size;
+			let cell_max = cell_min + Vec3::splat(self.cell_size);
+			let closest = center.clamp(cell_min, cell_max);
+			let furthest = Vec3::select(center.cmplt((cell_min + cell_max) / 2.0), cell_max, cell_min);
+			let (all, none) = if (closest - center).length_squared() > radius_squared {
+				(true, false)
+			} else {
+				(false, (furthest - center).length_squared() <= radius_squared)
+			};
+			entries.iter().filter(move |e| !none && (all || (e.position - center).length_squared() > radius_squared))
+		})
+	}
+}
+
+type Moved = Or<(Changed<Transform>, Changed<Interactable>)>;
+
+pub(super) fn update_spatial_grid(
+	mut grid: ResMut<SpatialGrid>,
+	objects: Query<(Entity, &Transform, &Interactable), Moved>,
+	mut removed: RemovedComponents<Interactable>,
+) {
+	for entity in removed.iter() {
+		grid.remove(entity);
+	}
+	for (entity, transform, interactable) in objects.iter() {
+		grid.update(entity, transform.translation, interactable.shape.bounding_radius());
+	}
+	grid.refresh_max_radius();
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Instant;
+
+	use rand::{Rng, SeedableRng, rngs::StdRng};
+
+	use super::*;
+
+	fn random_grid(rng: &mut StdRng, count: u32, extent: f32, max_radius: f32) -> (SpatialGrid, Vec<GridEntry>) {
+		let mut grid = SpatialGrid::default();
+		let entries: Vec<GridEntry> = (0..count).map(|i| GridEntry {
+			entity: Entity::from_raw(i),
+			position: Vec3::new(rng.gen_range(-extent..extent), rng.gen_range(-extent..extent), rng.gen_range(-extent..extent)),
+			radius: rng.gen_range(0.1..max_radius),
+		}).collect();
+		for entry in entries.iter() {
+			grid.update(entry.entity, entry.position, entry.radius);
+		}
+		(grid, entries)
+	}
+
+	fn brute_force_radius(entries: &[GridEntry], center: Vec3, radius: f32) -> Vec<Entity> {
+		let mut found: Vec<Entity> = entries.iter()
+			.filter(|e| (e.position - center).length() <= radius + e.radius)
+			.map(|e| e.entity)
+			.collect();
+		found.sort();
+		found
+	}
+
+	fn sorted(entries: impl Iterator<Item = Entity>) -> Vec<Entity> {
+		let mut entities: Vec<Entity> = entries.collect();
+		entities.sort();
+		entities
+	}
+
+	#[test]
+	fn queries_match_brute_force() {
+		let mut rng = StdRng::seed_from_u64(4);
+		let (grid, entries) = random_grid(&mut rng, 2000, 100.0, 5.0);
+		for _ in 0..50 {
+			let center = Vec3::new(rng.gen_range(-100.0..100.0), rng.gen_range(-100.0..100.0), rng.gen_range(-100.0..100.0));
+			let radius = rng.gen_range(1.0..40.0);
+			assert_eq!(sorted(grid.query_radius(center, radius).map(|e| e.entity)), brute_force_radius(&entries, center, radius));
+
+			let outside: Vec<Entity> = {
+				let mut found: Vec<Entity> = entries.iter().filter(|e| (e.position - center).length() > radius).map(|e| e.entity).collect();
+				found.sort();
+				found
+			};
+			assert_eq!(sorted(grid.outside_radius(center, radius).map(|e| e.entity)), outside);
+		}
+	}
+
+	#[test]
+	fn big_queries_walk_the_occupied_cells() {
+		let mut rng = StdRng::seed_from_u64(5);
+		let (mut grid, mut entries) = random_grid(&mut rng, 300, 200.0, 2.0);
+		let mut check = |grid: &SpatialGrid, entries: &[GridEntry]| for radius in [5.0, 150.0, 1e6] {
+			let center = Vec3::new(rng.gen_range(-200.0..200.0), rng.gen_range(-200.0..200.0), rng.gen_range(-200.0..200.0));
+			assert_eq!(sorted(grid.query_radius(center, radius).map(|e| e.entity)), brute_force_radius(entries, center, radius));
+		};
+		check(&grid, &entries);
+
+		// One huge hazard pads every query by its radius
+		let hazard = GridEntry {entity: Entity::from_raw(300), position: Vec3::new(500.0, 0.0, 0.0), radius: 120.0};
+		grid.update(hazard.entity, hazard.position, hazard.radius);
+		entries.push(hazard);
+		check(&grid, &entries);
+	}
+
+	#[test]
+	fn max_radius_shrinks_when_big_entries_leave() {
+		let mut grid = SpatialGrid::default();
+		grid.update(Entity::from_raw(0), Vec3::ZERO, 1.0);
+		grid.update(Entity::from_raw(1), Vec3::X * 50.0, 30.0);
+		assert_eq!(grid.max_radius, 30.0);
+
+		grid.remove(Entity::from_raw(1));
+		grid.refresh_max_radius();
+		assert_eq!(grid.max_radius, 1.0);
+
+		// Shrinking in place counts too
+		grid.update(Entity::from_raw(2), Vec3::ZERO, 8.0);
+		grid.update(Entity::from_raw(2), Vec3::ZERO, 2.0);
+		grid.refresh_max_radius();
+		assert_eq!(grid.max_radius, 2.0);
+	}
+
+	// Timing at the size the request asked for, `cargo test --release -- --ignored grid_timing --nocapture`
+	#[test]
+	#[ignore]
+	fn grid_timing_10k() {
+		let mut rng = StdRng::seed_from_u64(10_000);
+		let (mut grid, mut entries) = random_grid(&mut rng, 10_000, 500.0, 3.0);
+		let queries: Vec<Vec3> = (0..1000).map(|_| Vec3::new(rng.gen_range(-500.0..500.0), rng.gen_range(-500.0..500.0), rng.gen_range(-500.0..500.0))).collect();
+
+		let start = Instant::now();
+		for entry in entries.iter_mut() {
+			entry.position += Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+			grid.update(entry.entity, entry.position, entry.radius);
+		}
+		let update_time = start.elapsed();
+
+		let start = Instant::now();
+		let grid_hits: usize = queries.iter().map(|center| grid.query_radius(*center, 10.0).count()).sum();
+		let grid_time = start.elapsed();
+
+		let start = Instant::now();
+		let brute_hits: usize = queries.iter().map(|center| brute_force_radius(&entries, *center, 10.0).len()).sum();
+		let brute_time = start.elapsed();
+
+		println!("10k entries: moving all {:?}, 1000 queries {:?} on the grid against {:?} brute force", update_time, grid_time, brute_time);
+		assert_eq!(grid_hits, brute_hits);
+		assert!(grid_time < brute_time);
+	}
+}
@@ -0,0 +1,266 @@
+use bevy::{prelude::*, utils::StableHashSet};
+use crate::{prelude::*, GameState};
+
+mod grid;
+mod shape;
+
+pub use grid::SpatialGrid;
+pub use shape::{Shape, Collider};
+use grid::update_spatial_grid;
+
+// Simulation steps, these run in the fixed timestep schedule
+#[derive(SystemSet, Hash, Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct PhysicsSystemSet;
+
+// Per-frame systems that show the simulation through Transforms and report interactions
+#[derive(SystemSet, Hash, Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct InteractionSystemSet;
+
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+	fn build(&self, app: &mut App) {
+		app
+		.add_event::<PlayerInteractionEvent>()
+		.init_resource::<SpatialGrid>()
+		.init_resource::<InteractionPairs>()
+		.add_systems((
+			begin_physics_step,
+			velocity_integration, rotation_integration,
+			velocity_drag, angular_velocity_drag,
+		).chain().in_set(PhysicsSystemSet).distributive_run_if(in_state(GameState::Game)).in_schedule(CoreSchedule::FixedUpdate))
+		// Before the fixed steps, so bodies spawned last frame don't sit out however many steps this frame has
+		.add_system(attach_physics_state.in_base_set(CoreSet::PreUpdate).run_if(in_state(GameState::Game)))
+		.add_systems((
+			interpolate_transforms,
+			update_spatial_grid,
+			check_interaction_collisions
+		).chain().in_set(InteractionSystemSet).distributive_run_if(in_state(GameState::Game)));
+	}
+}
+
+// Simulated pose of a moving body, its Transform shows it interpolated between the last two steps
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct PhysicsState {
+	translation: Vec3,
+	rotation: Quat,
+	previous_translation: Vec3,
+	previous_rotation: Quat,
+	// What got written into the Transform last, anything else found there was put by someone else
+	rendered_translation: Vec3,
+	rendered_rotation: Quat,
+}
+
+impl PhysicsState {
+	fn new(transform: &Transform) -> Self {
+		PhysicsState {
+			translation: transform.translation,
+			rotation: transform.rotation,
+			previous_translation: transform.translation,
+			previous_rotation: transform.rotation,
+			rendered_translation: transform.translation,
+			rendered_rotation: transform.rotation,
+		}
+	}
+
+	// Takes over changes other systems made to the Transform, so teleporting and aiming things still works
+	fn adopt(&mut self, transform: &Transform) {
+		if transform.translation != self.rendered_translation {
+			self.translation = transform.translation;
+			self.previous_translation = transform.translation;
+			self.rendered_translation = transform.translation;
+		}
+		if transform.rotation != self.rendered_rotation {
+			self.rotation = transform.rotation;
+			self.previous_rotation = transform.rotation;
+			self.rendered_rotation = transform.rotation;
+		}
+	}
+}
+
+type Moving = Or<(With<Velocity>, With<AngularVelocity>)>;
+
+fn fixed_dt(fixed_time: &FixedTime) -> f32 {
+	fixed_time.period.as_secs_f32()
+}
+
+fn attach_physics_state(
+	mut commands: Commands,
+	bodies: Query<(Entity, &Transform), (Moving, Without<PhysicsState>)>,
+) {
+	for (entity, transform) in bodies.iter() {
+		commands.entity(entity).insert(PhysicsState::new(transform));
+	}
+}
+
+fn begin_physics_step(mut bodies: Query<(&Transform, &mut PhysicsState), Moving>) {
+	for (transform, mut state) in bodies.iter_mut() {
+		state.adopt(transform);
+		state.previous_translation = state.translation;
+		state.previous_rotation = state.rotation;
+	}
+}
+
+fn velocity_integration(
+	mut entities: Query<(&Velocity, &mut PhysicsState)>,
+	fixed_time: Res<FixedTime>
+) {
+	let dt = fixed_dt(&fixed_time);
+	for (v, mut state) in entities.iter_mut() {
+		state.translation += v.0 * dt;
+	}
+}
+
+fn velocity_drag(
+	mut entities: Query<(&mut Velocity, &VelocityDrag)>,
+	fixed_time: Res<FixedTime>
+) {
+	let dt = fixed_dt(&fixed_time);
+	for (mut v, d) in entities.iter_mut() {
+		v.0 *= f32::exp(-d.0 * dt);
+	}
+}
+
+fn rotation_integration(
+	mut entities: Query<(&AngularVelocity, &mut PhysicsState)>,
+	fixed_time: Res<FixedTime>
+) {
+	let dt = fixed_dt(&fixed_time);
+	for (v, mut state) in entities.iter_mut() {
+		let angle = v.length() * dt;
+		if v.length() > 0.00001 {
+			state.rotation = (Quat::from_axis_angle(v.normalize(), angle) * state.rotation).normalize();
+		}
+	}
+}
+
+fn angular_velocity_drag(
+	mut entities: Query<(&mut AngularVelocity, &AngularVelocityDrag)>,
+	fixed_time: Res<FixedTime>
+) {
+	let dt = fixed_dt(&fixed_time);
+	for (mut v, d) in entities.iter_mut() {
+		v.0 *= f32::exp(-d.0 * dt);
+	}
+}
+
+fn interpolate_transforms(
+	mut bodies: Query<(&mut Transform, &mut PhysicsState), Moving>,
+	fixed_time: Res<FixedTime>
+) {
+	let alpha = (fixed_time.accumulated().as_secs_f32() / fixed_dt(&fixed_time)).clamp(0.0, 1.0);
+	for (mut transform, mut state) in bodies.iter_mut() {
+		state.adopt(&transform);
+		transform.translation = state.previous_translation.lerp(state.translation, alpha);
+		transform.rotation = state.previous_rotation.slerp(state.rotation, alpha);
+		state.rendered_translation = transform.translation;
+		state.rendered_rotation = transform.rotation;
+	}
+}
+
+// Player and interactable pairs that overlapped as of the last check, stable hashing keeps replays in step
+#[derive(Resource, Debug, Default)]
+pub struct InteractionPairs(pub StableHashSet<(Entity, Entity)>);
+
+fn check_interaction_collisions(
+	player: Query<(Entity, &Transform, &Hull), With<Player>>,
+	interactables: Query<(&Transform, &Interactable)>,
+	grid: Res<SpatialGrid>,
+	mut pairs: ResMut<InteractionPairs>,
+	mut events: EventWriter<PlayerInteractionEvent>
+) {
+	let mut overlapping = StableHashSet::default();
+	for (player_entity, player_transform, hull) in player.iter() {
+		let hull = Collider::new(
+			hull.shape,
+			player_transform.translation + player_transform.rotation * hull.offset,
+			player_transform.rotation
+		);
+		// The grid only knows bounding spheres, the shapes themselves settle it
+		for entry in grid.query_radius(hull.center, hull.shape.bounding_radius()) {
+			let Ok((transform, interactable)) = interactables.get(entry.entity) else {
+				continue;
+			};
+			if hull.overlaps(&Collider::new(interactable.shape, transform.translation, transform.rotation)) {
+				overlapping.insert((player_entity, entry.entity));
+			}
+		}
+	}
+
+	for &(player, other) in overlapping.iter() {
+		let phase = if pairs.0.contains(&(player, other)) {
+			InteractionPhase::Ongoing
+		} else {
+			InteractionPhase::Started
+		};
+		events.send(PlayerInteractionEvent {player, other, phase});
+	}
+	for &(player, other) in pairs.0.difference(&overlapping) {
+		events.send(PlayerInteractionEvent {player, other, phase: InteractionPhase::Ended});
+	}
+	pairs.0 = overlapping;
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+
+	use bevy::time::TimeUpdateStrategy;
+
+	use super::*;
+
+	// Half a step past a whole number of them, so every frame rate ends up the same number of steps in
+	const SIMULATED: f64 = 2.0 + 1.0 / 120.0;
+
+	// Spawned a while in, like everything that shows up during the game
+	const SPAWN_AT: f64 = 0.5;
+
+	// Flies a tumbling, drifting body from SPAWN_AT until SIMULATED seconds at the given frame rate
+	fn simulate(fps: f64) -> (Transform, Velocity, AngularVelocity) {
+		let mut app = App::new();
+		app
+		.add_plugins(MinimalPlugins)
+		.add_state::<GameState>()
+		.add_plugin(PhysicsPlugin)
+		.insert_resource(FixedTime::new_from_secs(1.0 / 60.0))
+		.insert_resource(NextState(Some(GameState::Game)));
+
+		// The first update only starts the clock
+		let start = Instant::now();
+		app.insert_resource(TimeUpdateStrategy::ManualInstant(start));
+		app.update();
+
+		let mut body = None;
+		let frames = (SIMULATED * fps).ceil() as u32;
+		for frame in 1..=frames {
+			if body.is_none() && frame as f64 / fps > SPAWN_AT {
+				body = Some(app.world.spawn((
+					Transform::from_xyz(1.0, 2.0, 3.0),
+					Velocity(Vec3::new(10.0, -4.0, 2.0)),
+					VelocityDrag(0.5),
+					AngularVelocity(Vec3::new(0.3, 1.2, -0.4)),
+					AngularVelocityDrag(0.2),
+				)).id());
+			}
+			let elapsed = (frame as f64 / fps).min(SIMULATED);
+			app.insert_resource(TimeUpdateStrategy::ManualInstant(start + Duration::from_secs_f64(elapsed)));
+			app.update();
+		}
+
+		let entity = app.world.entity(body.unwrap());
+		(*entity.get::<Transform>().unwrap(), *entity.get::<Velocity>().unwrap(), *entity.get::<AngularVelocity>().unwrap())
+	}
+
+	#[test]
+	fn frame_rate_does_not_change_the_outcome() {
+		let (transform, velocity, angular) = simulate(30.0);
+		assert!(transform.translation.distance(Vec3::new(1.0, 2.0, 3.0)) > 10.0, "body barely moved: {:?}", transform);
+		for fps in [60.0, 144.0] {
+			let (other_transform, other_velocity, other_angular) = simulate(fps);
+			assert!(other_transform.translation.abs_diff_eq(transform.translation, 1e-3), "{} FPS ended at {} instead of {}", fps, other_transform.translation, transform.translation);
+			assert!(other_transform.rotation.abs_diff_eq(transform.rotation, 1e-4), "{} FPS ended turned {} instead of {}", fps, other_transform.rotation, transform.rotation);
+			assert!(other_velocity.0.abs_diff_eq(velocity.0, 1e-5), "{} FPS ended at speed {} instead of {}", fps, other_velocity.0, velocity.0);
+			assert!(other_angular.0.abs_diff_eq(angular.0, 1e-5), "{} FPS ended spinning {} instead of {}", fps, other_angular.0, angular.0);
+		}
+	}
+}
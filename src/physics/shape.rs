@@ -0,0 +1,270 @@
+use bevy::prelude::*;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Shape {
+	Sphere {radius: f32},
+	// Stays lined up with the world axes whatever the rotation
+	Aabb {half_extents: Vec3},
+	// Turns with the rotation
+	Obb {half_extents: Vec3},
+	// Runs along the local Y axis, half_length reaches from the center to the middle of either cap
+	Capsule {half_length: f32, radius: f32},
+}
+
+impl Default for Shape {
+	fn default() -> Self {
+		Shape::Sphere {radius: 0.0}
+	}
+}
+
+impl Shape {
+	// Radius of a sphere around the center that holds the whole shape, what the broadphase works with
+	pub fn bounding_radius(&self) -> f32 {
+		match *self {
+			Shape::Sphere {radius} => radius,
+			Shape::Aabb {half_extents} | Shape::Obb {half_extents} => half_extents.length(),
+			Shape::Capsule {half_length, radius} => half_length + radius,
+		}
+	}
+}
+
+// A shape put somewhere in the world
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Collider {
+	pub shape: Shape,
+	pub center: Vec3,
+	pub rotation: Quat,
+}
+
+// Everything boils down to one of these, spheres and capsules are a segment with some thickness
+enum Primitive {
+	Rounded {a: Vec3, b: Vec3, radius: f32},
+	Box {center: Vec3, rotation: Quat, half_extents: Vec3},
+}
+
+impl Collider {
+	pub fn new(shape: Shape, center: Vec3, rotation: Quat) -> Self {
+		Collider {shape, center, rotation}
+	}
+
+	fn primitive(&self) -> Primitive {
+		match self.shape {
+			Shape::Sphere {radius} => Primitive::Rounded {a: self.center, b: self.center, radius},
+			Shape::Capsule {half_length, radius} => {
+				let axis = self.rotation * Vec3::Y * half_length;
+				Primitive::Rounded {a: self.center - axis, b: self.center + axis, radius}
+			},
+			Shape::Aabb {half_extents} => Primitive::Box {center: self.center, rotation: Quat::IDENTITY, half_extents},
+			Shape::Obb {half_extents} => Primitive::Box {center: self.center, rotation: self.rotation, half_extents},
+		}
+	}
+
+	// Touching counts as overlapping
+	pub fn overlaps(&self, other: &Collider) -> bool {
+		match (self.primitive(), other.primitive()) {
+			(Primitive::Rounded {a: a0, b: b0, radius: r0}, Primitive::Rounded {a: a1, b: b1, radius: r1}) => {
+				let reach = r0 + r1;
+				segment_segment_distance_squared(a0, b0, a1, b1) <= reach * reach
+			},
+			(Primitive::Rounded {a, b, radius}, Primitive::Box {center, rotation, half_extents})
+			| (Primitive::Box {center, rotation, half_extents}, Primitive::Rounded {a, b, radius}) => {
+				// Into the box's frame, where it's axis aligned around the origin
+				let inverse = rotation.inverse();
+				let a = inverse * (a - center);
+				let b = inverse * (b - center);
+				segment_box_distance_squared(a, b, half_extents) <= radius * radius
+			},
+			(Primitive::Box {center: c0, rotation: q0, half_extents: h0}, Primitive::Box {center: c1, rotation: q1, half_extents: h1}) => {
+				boxes_overlap(c0, q0, h0, c1, q1, h1)
+			},
+		}
+	}
+}
+
+fn point_box_distance_squared(point: Vec3, half_extents: Vec3) -> f32 {
+	(point.abs() - half_extents).max(Vec3::ZERO).length_squared()
+}
+
+// Squared distance between segments ab and cd, after Ericson's Real-Time Collision Detection 5.1.9
+fn segment_segment_distance_squared(a: Vec3, b: Vec3, c: Vec3, d: Vec3) -> f32 {
+	const EPSILON: f32 = 1e-12;
+	let d0 = b - a;
+	let d1 = d - c;
+	let r = a - c;
+	let l0 = d0.length_squared();
+	let l1 = d1.length_squared();
+	let f = d1.dot(r);
+
+	let (s, t) = if l0 <= EPSILON && l1 <= EPSILON {
+		(0.0, 0.0)
+	} else if l0 <= EPSILON {
+		(0.0, (f / l1).clamp(0.0, 1.0))
+	} else {
+		let e = d0.dot(r);
+		if l1 <= EPSILON {
+			((-e / l0).clamp(0.0, 1.0), 0.0)
+		} else {
+			let k = d0.dot(d1);
+			let denominator = l0 * l1 - k * k;
+			// Parallel segments have no single closest pair, any s works
+			let mut s = if denominator > EPSILON {((k * f - e * l1) / denominator).clamp(0.0, 1.0)} else {0.0};
+			let mut t = (k * s + f) / l1;
+			if t < 0.0 {
+				t = 0.0;
+				s = (-e / l0).clamp(0.0, 1.0);
+			} else if t > 1.0 {
+				t = 1.0;
+				s = ((k - e) / l0).clamp(0.0, 1.0);
+			}
+			(s, t)
+		}
+	};
+	(a + d0 * s - (c + d1 * t)).length_squared()
+}
+
+// Slab test of the segment against the box centered on the origin
+fn segment_hits_box(a: Vec3, b: Vec3, half_extents: Vec3) -> bool {
+	let direction = b - a;
+	let (mut enter, mut exit) = (0.0_f32, 1.0_f32);
+	for axis in 0..3 {
+		if direction[axis].abs() < 1e-12 {
+			if a[axis].abs() > half_extents[axis] {
+				return false;
+			}
+			continue;
+		}
+		let t0 = (-half_extents[axis] - a[axis]) / direction[axis];
+		let t1 = (half_extents[axis] - a[axis]) / direction[axis];
+		enter = enter.max(t0.min(t1));
+		exit = exit.min(t0.max(t1));
+		if enter > exit {
+			return false;
+		}
+	}
+	true
+}
+
+// Squared distance from segment ab to the box centered on the origin
+fn segment_box_distance_squared(a: Vec3, b: Vec3, half_extents: Vec3) -> f32 {
+	if segment_hits_box(a, b, half_extents) {
+		return 0.0;
+	}
+	// Apart from each other the closest pair is an endpoint against the box or the segment against one of its edges
+	let endpoints = point_box_distance_squared(a, half_extents).min(point_box_distance_squared(b, half_extents));
+	let h = half_extents;
+	let mut closest = endpoints;
+	for axis in 0..3 {
+		let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+		for (su, sv) in [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)] {
+			let mut start = Vec3::ZERO;
+			start[u] = su * h[u];
+			start[v] = sv * h[v];
+			let mut end = start;
+			start[axis] = -h[axis];
+			end[axis] = h[axis];
+			closest = closest.min(segment_segment_distance_squared(a, b, start, end));
+		}
+	}
+	closest
+}
+
+// Separating axis test, the face normals of both boxes and their cross products are all the axes that can separate them
+fn boxes_overlap(c0: Vec3, q0: Quat, h0: Vec3, c1: Vec3, q1: Quat, h1: Vec3) -> bool {
+	let axes0 = [q0 * Vec3::X, q0 * Vec3::Y, q0 * Vec3::Z];
+	let axes1 = [q1 * Vec3::X, q1 * Vec3::Y, q1 * Vec3::Z];
+	let offset = c1 - c0;
+
+	let separates = |axis: Vec3| {
+		let projection = |axes: &[Vec3; 3], h: Vec3| {
+			(0..3).map(|i| h[i] * axes[i].dot(axis).abs()).sum::<f32>()
+		};
+		offset.dot(axis).abs() > projection(&axes0, h0) + projection(&axes1, h1)
+	};
+
+	let faces = axes0.iter().chain(axes1.iter()).copied();
+	// Crosses of (nearly) parallel edges vanish, the face axes already cover those cases
+	let edges = axes0.iter()
+		.flat_map(|a| axes1.iter().map(move |b| a.cross(*b)))
+		.filter(|axis| axis.length_squared() > 1e-8);
+	!faces.chain(edges).any(separates)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::f32::consts::FRAC_PI_4;
+
+	use super::*;
+
+	const MARGIN: f32 = 0.01;
+	// Touching, give or take what rotating things costs in rounding
+	const TOUCHING: f32 = -1e-5;
+
+	// One of every shape, with how far it reaches along X from its center
+	// Turned only around X so the reaches come from faces, sides and caps
+	fn shapes() -> [(&'static str, Shape, Quat, f32); 4] {
+		[
+			("sphere", Shape::Sphere {radius: 1.0}, Quat::IDENTITY, 1.0),
+			// Rotation is ignored
+			("aabb", Shape::Aabb {half_extents: Vec3::new(1.5, 0.5, 2.0)}, Quat::from_rotation_y(0.7), 1.5),
+			("obb", Shape::Obb {half_extents: Vec3::new(0.75, 1.0, 2.0)}, Quat::from_rotation_x(0.6), 0.75),
+			("capsule", Shape::Capsule {half_length: 2.0, radius: 0.5}, Quat::from_rotation_x(1.1), 0.5),
+		]
+	}
+
+	// Shapes tilted so their reach along X comes from corners, edges and caps instead of faces
+	fn tilted_shapes() -> [(&'static str, Shape, Quat, f32); 2] {
+		[
+			("tilted obb", Shape::Obb {half_extents: Vec3::splat(1.0)}, Quat::from_rotation_z(FRAC_PI_4), std::f32::consts::SQRT_2),
+			("lying capsule", Shape::Capsule {half_length: 1.0, radius: 0.5}, Quat::from_rotation_z(-std::f32::consts::FRAC_PI_2), 1.5),
+		]
+	}
+
+	// Puts the second shape gap further along X than where the two would just touch
+	fn overlap_at(first: (&str, Shape, Quat, f32), second: (&str, Shape, Quat, f32), gap: f32) -> bool {
+		let a = Collider::new(first.1, Vec3::new(3.0, -1.0, 2.0), first.2);
+		let b = Collider::new(second.1, a.center + Vec3::X * (first.3 + second.3 + gap), second.2);
+		let overlaps = a.overlaps(&b);
+		assert_eq!(overlaps, b.overlaps(&a), "{} and {} disagree on overlapping", first.0, second.0);
+		overlaps
+	}
+
+	#[test]
+	fn every_pair_touches() {
+		for first in shapes().into_iter().chain(tilted_shapes()) {
+			for second in shapes().into_iter().chain(tilted_shapes()) {
+				assert!(overlap_at(first, second, TOUCHING), "{} and {} should touch", first.0, second.0);
+			}
+		}
+	}
+
+	#[test]
+	fn every_pair_separates() {
+		for first in shapes().into_iter().chain(tilted_shapes()) {
+			for second in shapes().into_iter().chain(tilted_shapes()) {
+				assert!(!overlap_at(first, second, MARGIN), "{} and {} should be apart", first.0, second.0);
+			}
+		}
+	}
+
+	#[test]
+	fn every_pair_just_overlaps() {
+		for first in shapes().into_iter().chain(tilted_shapes()) {
+			for second in shapes().into_iter().chain(tilted_shapes()) {
+				assert!(overlap_at(first, second, -MARGIN), "{} and {} should overlap", first.0, second.0);
+			}
+		}
+	}
+
+	#[test]
+	fn boxes_apart_along_an_edge_axis() {
+		// Two cubes standing on their edges, crossed, with only the edge to edge axis telling them apart
+		let half = 1.0;
+		let a = Collider::new(Shape::Obb {half_extents: Vec3::splat(half)}, Vec3::ZERO, Quat::from_rotation_x(FRAC_PI_4));
+		let reach = half * std::f32::consts::SQRT_2;
+		let b_rotation = Quat::from_rotation_z(FRAC_PI_4);
+		for (gap, expected) in [(MARGIN, false), (-MARGIN, true)] {
+			let b = Collider::new(Shape::Obb {half_extents: Vec3::splat(half)}, Vec3::Y * (2.0 * reach + gap), b_rotation);
+			assert_eq!(a.overlaps(&b), expected, "gap {}", gap);
+		}
+	}
+}
@@ -1,22 +1,56 @@
 use std::f32::consts::PI;
 
-use bevy::{prelude::*, core_pipeline::clear_color::ClearColorConfig, render::camera::{RenderTarget, Viewport}};
-use crate::{prelude::*, physics::PhysicsSystemSet, GameState};
+use bevy::{prelude::*, core_pipeline::clear_color::ClearColorConfig, render::{camera::{RenderTarget, Viewport}, primitives::Aabb, view::RenderLayers}};
+use crate::{prelude::*, camera_layout::CameraRole, physics::{PhysicsSystemSet, InteractionSystemSet, Shape}, input::{ActionState, Action}, thoughts::Emotion, GameState};
 
 #[derive(Component, Default, Eq, PartialEq, Debug, Clone, Copy)]
 pub struct PlayerHarness;
 
+#[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum ShipState {
+	#[default]
+	Cruising,
+	Hyperspace,
+}
+
 pub struct PlayerBehaviourPlugin;
 
+const BOOST: BoostParameters = BoostParameters {
+	impulse: 500.0,
+	cost: 0.35,
+	cooldown: 2.0,
+	merge_recharge: 0.5,
+	positive_thought_recharge: 0.05,
+};
+
 impl Plugin for PlayerBehaviourPlugin {
 	fn build(&self, app: &mut App) {
 		app
+		.insert_resource(ShipHandling {
+			thrust_acceleration: 0.01,
+			turn_acceleration: 0.05,
+		})
+		.insert_resource(SpeedLimits {
+			soft_cap: 200.0,
+			softness: 2.0,
+			hyperspace_entry: 150.0,
+			hyperspace_exit: 100.0,
+		})
+		.insert_resource(BOOST)
+		.insert_resource(BoostFuel::new(&BOOST))
+		.add_event::<BoostEvent>()
+		.add_systems((
+			player_controller,
+			limit_speed,
+		).chain().before(PhysicsSystemSet).distributive_run_if(in_state(GameState::Game)).in_schedule(CoreSchedule::FixedUpdate))
 		.add_systems((
-			player_controller.run_if(in_state(GameState::Game)),
-			player_transform.after(PhysicsSystemSet),
+			player_transform.after(InteractionSystemSet),
 			clear_on_refresh,
+			fit_hull,
+			player_boost,
+			recharge_boost,
+			update_ship_state,
 		).distributive_run_if(in_state(GameState::Game)))
-		.add_system(player_boost.run_if(on_event::<ThoughtCutsceneEndEvent>()).run_if(in_state(GameState::Game)))
 		;
 	}
 }
@@ -36,6 +70,8 @@ pub fn spawn_player_and_cameras(
 	// Outer player transform object
 	commands
 	.spawn((Player,
+		// A point until the ship model loads and the hull gets fitted to it
+		Hull::default(),
 		render_target.texture.clone(),
 		Velocity(Vec3::ZERO), VelocityDrag(0.1),
 		AngularVelocity(Vec3::ZERO), AngularVelocityDrag(0.1),
@@ -68,7 +104,7 @@ pub fn spawn_player_and_cameras(
 			transform: Transform::from_translation(Vec3::new(0.0, 2.0, 8.0)).looking_at(Vec3::ZERO, Vec3::Y),
 			..Default::default()
 		})
-		.insert(Name::new("3rd Person Camera"));
+		.insert((CameraRole::Chase, Name::new("3rd Person Camera")));
 		// Unrotated transform
 		player_holder.spawn((PlayerHarness, SpatialBundle::default())).insert(Name::new("Orthocams"))
 		.with_children(|parent| {
@@ -84,7 +120,7 @@ pub fn spawn_player_and_cameras(
 				transform: Transform::from_translation(10.0 * Vec3::Z).looking_at(Vec3::ZERO, Vec3::Y),
 				..Default::default()
 			})
-			.insert(Name::new("Front View Camera"));
+			.insert((CameraRole::Front, Name::new("Front View Camera")));
 			parent.spawn(Camera3dBundle {
 				camera: Camera {
 					viewport: Some(Viewport {physical_position: half_size, physical_size: half_size, ..Default::default()}),
@@ -96,8 +132,8 @@ pub fn spawn_player_and_cameras(
 				transform: Transform::from_translation(10.0 * Vec3::Y).looking_at(Vec3::ZERO, Vec3::X),
 				..Default::default()
 			})
-			.insert(Name::new("Top Down View Camera"));
-			parent.spawn((Camera3dBundle {
+			.insert((CameraRole::Top, Name::new("Top Down View Camera")));
+			parent.spawn(Camera3dBundle {
 				camera: Camera {
 					viewport: Some(Viewport {physical_position: UVec2::new(0, half_size.y), physical_size: half_size, ..Default::default()}),
 					target: RenderTarget::Image(render_target.texture.clone()),
@@ -107,64 +143,216 @@ pub fn spawn_player_and_cameras(
 				camera_3d: Camera3d {clear_color: ClearColorConfig::None, ..Default::default()},
 				transform: Transform::from_translation(10.0 * Vec3::X).looking_at(Vec3::ZERO, Vec3::Z),
 				..Default::default()
-			},
-			ClearCamera(false)))
-			.insert(Name::new("Side View Camera"));
+			})
+			.insert((CameraRole::Side, Name::new("Side View Camera")));
 		});
 	});
+
+	// Renders nothing, only there to wipe the whole texture before the views draw into it
+	commands.spawn((Camera3dBundle {
+		camera: Camera {
+			target: RenderTarget::Image(render_target.texture.clone()),
+			order: -100, is_active: true, ..Default::default()
+		},
+		camera_3d: Camera3d {clear_color: ClearColorConfig::None, ..Default::default()},
+		..Default::default()
+	},
+	RenderLayers::none(),
+	ClearCamera(false),
+	))
+	.insert(Name::new("Clear Camera"));
 }
 
 pub fn player_controller(
-	input: Res<Input<KeyCode>>,
+	actions: Res<ActionState>,
+	handling: Res<ShipHandling>,
 	mut player: Query<(&Transform, &mut Velocity, &mut AngularVelocity), With<Player>>
 ) {
 	for (transform, mut velocity, mut angular) in player.iter_mut() {
-		if input.pressed(KeyCode::W) {
-			velocity.0 += 0.01 * transform.forward();
-		}
-		if input.pressed(KeyCode::S) {
-			velocity.0 -= 0.01 * transform.forward();
-		}
-		if input.pressed(KeyCode::D) {
-			angular.0 -= 0.05 * transform.up();
-		}
-		if input.pressed(KeyCode::A) {
-			angular.0 += 0.05 * transform.up();
-		}
-		if input.pressed(KeyCode::Q) {
-			angular.0 -= 0.05 * transform.forward();
-		}
-		if input.pressed(KeyCode::E) {
-			angular.0 += 0.05 * transform.forward();
+		velocity.0 += handling.thrust_acceleration * actions.value(Action::Thrust) * transform.forward();
+		angular.0 += handling.turn_acceleration * actions.value(Action::Yaw) * transform.up();
+		angular.0 += handling.turn_acceleration * actions.value(Action::Roll) * transform.forward();
+		angular.0 += handling.turn_acceleration * actions.value(Action::Pitch) * transform.right();
+	}
+}
+
+fn limit_speed(
+	mut player: Query<&mut Velocity, With<Player>>,
+	limits: Res<SpeedLimits>,
+	fixed_time: Res<FixedTime>,
+) {
+	let dt = fixed_time.period.as_secs_f32();
+	for mut velocity in player.iter_mut() {
+		let speed = velocity.length();
+		if speed > limits.soft_cap {
+			let capped = limits.soft_cap + (speed - limits.soft_cap) * f32::exp(-limits.softness * dt);
+			velocity.0 *= capped / speed;
 		}
 	}
 }
 
+fn update_ship_state(
+	player: Query<&Velocity, With<Player>>,
+	limits: Res<SpeedLimits>,
+	state: Res<State<ShipState>>,
+	mut next_state: ResMut<NextState<ShipState>>,
+) {
+	let Ok(velocity) = player.get_single() else {
+		return;
+	};
+	let speed = velocity.length();
+	match state.0 {
+		ShipState::Cruising if speed > limits.hyperspace_entry => next_state.set(ShipState::Hyperspace),
+		ShipState::Hyperspace if speed < limits.hyperspace_exit => next_state.set(ShipState::Cruising),
+		_ => {},
+	}
+}
+
 pub fn player_transform(mut harness: Query<&mut Transform, With<PlayerHarness>>) {
 	for mut transform in harness.iter_mut() {
 		transform.look_to(Vec3::NEG_Z, Vec3::Y);
 	}
 }
 
-fn player_boost(mut player: Query<(&Transform, &mut Velocity), With<Player>>, mut event: EventReader<ThoughtCutsceneEndEvent>) {
-	if event.is_empty() {
+// Thrusting backwards boosts backwards, anything else goes forwards
+fn thrust_direction(transform: &Transform, actions: &ActionState) -> Vec3 {
+	if actions.value(Action::Thrust) < 0.0 {
+		-transform.forward()
+	} else {
+		transform.forward()
+	}
+}
+
+fn player_boost(
+	mut player: Query<(&Transform, &mut Velocity), With<Player>>,
+	actions: Res<ActionState>,
+	parameters: Res<BoostParameters>,
+	mut fuel: ResMut<BoostFuel>,
+	time: Res<Time>,
+	mut events: EventWriter<BoostEvent>,
+) {
+	fuel.cooldown.tick(time.delta());
+	if !actions.just_pressed(Action::Boost) || !fuel.cooldown.finished() || fuel.fuel < parameters.cost {
 		return;
 	}
-	event.clear();
 	for (transform, mut velocity) in player.iter_mut() {
-		velocity.0 += 500.0 * transform.forward()
+		velocity.0 += parameters.impulse * thrust_direction(transform, &actions);
+	}
+	events.send(BoostEvent);
+	fuel.fuel -= parameters.cost;
+	fuel.cooldown.reset();
+}
+
+fn recharge_boost(
+	parameters: Res<BoostParameters>,
+	mut fuel: ResMut<BoostFuel>,
+	mut merges: EventReader<ThoughtCutsceneEndEvent>,
+	mut collected: EventReader<ThoughtCollectedEvent>,
+) {
+	for _ in merges.iter() {
+		fuel.add(parameters.merge_recharge);
+	}
+	for event in collected.iter().filter(|event| event.thought.emotion == Emotion::Positive) {
+		fuel.add(parameters.positive_thought_recharge * event.thought.intensity as f32);
 	}
 }
 
 fn clear_on_refresh(
-	keyboard: Res<Input<KeyCode>>,
+	actions: Res<ActionState>,
 	mut clear_camera_query: Query<&mut Camera3d, With<ClearCamera>>,
 ) {
 	let mut camera = clear_camera_query.single_mut();
-	if keyboard.just_pressed(KeyCode::C) {
+	if actions.just_pressed(Action::ClearScreen) {
 		camera.clear_color = ClearColorConfig::default();
 	}
-	if keyboard.just_released(KeyCode::C) {
+	if actions.just_released(Action::ClearScreen) {
 		camera.clear_color = ClearColorConfig::None;
 	}
 }
+
+// Wraps the ship model in a box once all of its meshes have bounds, and again whenever any of them change
+fn fit_hull(
+	mut player: Query<(Entity, &GlobalTransform, &mut Hull), With<Player>>,
+	children: Query<&Children>,
+	meshes: Query<(Option<&Aabb>, &GlobalTransform), With<Handle<Mesh>>>,
+	changed: Query<(), Changed<Aabb>>,
+) {
+	for (player_entity, player_transform, mut hull) in player.iter_mut() {
+		let parts: Vec<Entity> = children.iter_descendants(player_entity).filter(|child| meshes.contains(*child)).collect();
+		if !parts.iter().any(|part| changed.contains(*part)) {
+			continue;
+		}
+		// The scene hands out bounds over a few frames, fitting before they're all in would only cover part of the ship
+		let bounds: Option<Vec<(&Aabb, &GlobalTransform)>> = parts.iter().map(|part| {
+			let (aabb, transform) = meshes.get(*part).ok()?;
+			Some((aabb?, transform))
+		}).collect();
+		let Some(bounds) = bounds else {
+			continue;
+		};
+
+		let to_local = player_transform.affine().inverse();
+		let mut min = Vec3::splat(f32::INFINITY);
+		let mut max = Vec3::splat(f32::NEG_INFINITY);
+		for (aabb, transform) in bounds {
+			let center = Vec3::from(aabb.center);
+			let half_extents = Vec3::from(aabb.half_extents);
+			for corner in 0..8 {
+				let sign = Vec3::new(
+					if corner & 1 == 0 {-1.0} else {1.0},
+					if corner & 2 == 0 {-1.0} else {1.0},
+					if corner & 4 == 0 {-1.0} else {1.0},
+				);
+				let point = to_local.transform_point3(transform.transform_point(center + sign * half_extents));
+				min = min.min(point);
+				max = max.max(point);
+			}
+		}
+		if min.cmple(max).all() {
+			hull.shape = Shape::Obb {half_extents: (max - min) / 2.0};
+			hull.offset = (max + min) / 2.0;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn mesh_part(app: &mut App, parent: Entity, translation: Vec3) -> Entity {
+		let part = app.world.spawn((Handle::<Mesh>::default(), GlobalTransform::from_translation(translation))).id();
+		app.world.entity_mut(parent).push_children(&[part]);
+		part
+	}
+
+	fn hull(app: &App, player: Entity) -> Hull {
+		*app.world.get::<Hull>(player).unwrap()
+	}
+
+	#[test]
+	fn hull_waits_for_every_mesh_and_refits_when_bounds_change() {
+		let mut app = App::new();
+		app.add_system(fit_hull);
+
+		let player = app.world.spawn((Player, Hull::default(), GlobalTransform::IDENTITY)).id();
+		let nose = mesh_part(&mut app, player, Vec3::Z * -4.0);
+		let tail = mesh_part(&mut app, player, Vec3::Z * 4.0);
+		// The camera has no mesh and never gets bounds
+		let camera = app.world.spawn(GlobalTransform::IDENTITY).id();
+		app.world.entity_mut(player).push_children(&[camera]);
+
+		let unit = Aabb::from_min_max(Vec3::splat(-1.0), Vec3::splat(1.0));
+		app.world.entity_mut(nose).insert(unit);
+		app.update();
+		assert_eq!(hull(&app, player), Hull::default());
+
+		app.world.entity_mut(tail).insert(unit);
+		app.update();
+		assert_eq!(hull(&app, player), Hull {shape: Shape::Obb {half_extents: Vec3::new(1.0, 1.0, 5.0)}, offset: Vec3::ZERO});
+
+		// Bounds that change later on still get picked up
+		app.world.entity_mut(tail).insert(Aabb::from_min_max(Vec3::splat(-1.0), Vec3::new(1.0, 1.0, 3.0)));
+		app.update();
+		assert_eq!(hull(&app, player), Hull {shape: Shape::Obb {half_extents: Vec3::new(1.0, 1.0, 6.0)}, offset: Vec3::Z});
+	}
+}
@@ -9,7 +9,7 @@ mod effect_imports {
 			sprite::Material2d,
 		},
 		
-		super::super::{PostProcessingEffectMaterial, PostProcessingEffect}		
+		super::super::{PostProcessingEffectMaterial, PostProcessingEffect, PostProcessingError}		
 	};
 }
 
@@ -69,9 +69,9 @@ pub mod test {
 
 		fn n_slots() -> usize { 1 }
 
-		fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(),()> {
+		fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(), PostProcessingError> {
 			if slot > 0 {
-				Err(())
+				Err(PostProcessingError::UnknownSlot {slot, slots: Self::n_slots()})
 			} else {
 				self.source_image = texture;
 				Ok(())
@@ -126,9 +126,9 @@ pub mod flip {
 
 		fn n_slots() -> usize { 1 }
 
-		fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(),()> {
+		fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(), PostProcessingError> {
 			if slot > 0 {
-				Err(())
+				Err(PostProcessingError::UnknownSlot {slot, slots: Self::n_slots()})
 			} else {
 				self.source_image = texture;
 				Ok(())
@@ -183,9 +183,9 @@ pub mod jpeg {
 
 		fn n_slots() -> usize { 1 }
 
-		fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(),()> {
+		fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(), PostProcessingError> {
 			if slot > 0 {
-				Err(())
+				Err(PostProcessingError::UnknownSlot {slot, slots: Self::n_slots()})
 			} else {
 				self.source_image = texture;
 				Ok(())
@@ -236,9 +236,9 @@ pub mod jpeg {
 
 		fn n_slots() -> usize { 1 }
 
-		fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(),()> {
+		fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(), PostProcessingError> {
 			if slot > 0 {
-				Err(())
+				Err(PostProcessingError::UnknownSlot {slot, slots: Self::n_slots()})
 			} else {
 				self.source_image = texture;
 				Ok(())
@@ -293,9 +293,9 @@ pub mod dither {
 
 		fn n_slots() -> usize { 1 }
 
-		fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(),()> {
+		fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(), PostProcessingError> {
 			if slot > 0 {
-				Err(())
+				Err(PostProcessingError::UnknownSlot {slot, slots: Self::n_slots()})
 			} else {
 				self.source_image = texture;
 				Ok(())
@@ -313,6 +313,8 @@ pub mod feedback {
 		pub time: f32,
 		pub alpha: f32,
 		pub beta: f32,
+		// 0 at cruising speed, 1 in full hyperspace
+		pub hyperspace: f32,
 	}
 
 	impl Default for Effect {
@@ -322,6 +324,7 @@ pub mod feedback {
 				time: 0.0,
 				alpha: 0.42,
 				beta: 0.25,
+				hyperspace: 0.0,
 			}
 		}
 	}
@@ -337,6 +340,7 @@ pub mod feedback {
 			material.data.time = self.time;
 			material.data.alpha = self.alpha;
 			material.data.beta = self.beta;
+			material.data.hyperspace = self.hyperspace;
 		}
 	
 		fn get_handle(&self) -> Handle<Self::MaterialType> { self.material_handle.clone() }
@@ -347,7 +351,7 @@ pub mod feedback {
 		time: f32,
 		alpha: f32,
 		beta: f32,
-		valued: f32
+		hyperspace: f32
 	}
 	
 	/// Our custom post processing material
@@ -381,9 +385,9 @@ pub mod feedback {
 
 		fn n_slots() -> usize { 2 }
 
-		fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(),()> {
+		fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(), PostProcessingError> {
 			if slot > 1 {
-				Err(())
+				Err(PostProcessingError::UnknownSlot {slot, slots: Self::n_slots()})
 			} else if slot == 0 {
 				self.source_image = texture;
 				Ok(())
@@ -394,3 +398,90 @@ pub mod feedback {
 		}
 	}		
 }
+
+pub mod limiter {
+	use super::effect_imports::*;
+
+	#[derive(Component)]
+	pub struct Effect {
+		material_handle: Handle<Material>,
+		// Most any colour channel of a pixel may move from one frame to the next
+		pub max_change: f32,
+	}
+
+	impl Default for Effect {
+		fn default() -> Self {
+			Self {
+				material_handle: Default::default(),
+				max_change: 1.0,
+			}
+		}
+	}
+	
+	impl PostProcessingEffect for Effect {
+		type MaterialType = Material;
+
+		fn from_handle(handle: Handle<Self::MaterialType>) -> Self {
+			Effect { material_handle: handle, ..default() }
+		}
+	
+		fn update_info(&self, material: &mut Self::MaterialType) {
+			material.data.max_change = self.max_change;
+		}
+	
+		fn get_handle(&self) -> Handle<Self::MaterialType> { self.material_handle.clone() }
+	}
+
+	// Padded to 16 bytes, WebGL2 won't bind smaller uniform buffers
+	#[derive(ShaderType, Default, Clone, Copy)]
+	struct MaterialInner {
+		max_change: f32,
+		pad_a: f32,
+		pad_b: f32,
+		pad_c: f32
+	}
+	
+	/// Our custom post processing material
+	#[derive(AsBindGroup, TypeUuid, Clone)]
+	#[uuid = "e42fa9e5-b1fb-4c89-a908-548715a597d5"]
+	pub struct Material {
+		#[texture(0)]
+		#[sampler(1)]
+		source_image: Handle<Image>,
+		#[texture(2)]
+		#[sampler(3)]
+		previous_image: Handle<Image>,
+		#[uniform(4)]
+		data: MaterialInner,
+	}
+	
+	impl Material2d for Material {
+		fn fragment_shader() -> ShaderRef {
+			"shaders/post_processing/limiter.wgsl".into()
+		}
+	}
+	
+	impl PostProcessingEffectMaterial for Material {
+		fn new() -> Self {
+			Material { 
+				source_image: Handle::default(),
+				previous_image: Handle::default(),
+				data: MaterialInner::default()
+			}
+		}
+
+		fn n_slots() -> usize { 2 }
+
+		fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(), PostProcessingError> {
+			if slot > 1 {
+				Err(PostProcessingError::UnknownSlot {slot, slots: Self::n_slots()})
+			} else if slot == 0 {
+				self.source_image = texture;
+				Ok(())
+			} else {
+				self.previous_image = texture;
+				Ok(())
+			}
+		}
+	}		
+}
@@ -0,0 +1,333 @@
+use std::fmt::Display;
+
+use bevy::{prelude::*, reflect::TypeUuid, asset::{AssetLoader, LoadContext, LoadedAsset}, utils::{BoxedFuture, HashSet}};
+use serde::Deserialize;
+
+use crate::resources::MainRenderTexture;
+
+use super::{PostProcessingEffect, PostProcessingEffectMaterial, PostProcessingError, EffectAssociatedCameraID, effects::*};
+use super::setup::{EffectOutput, spawn_effect, link_effect, link_texture};
+
+// Effects take render layers from the top down, the low ones are left for the game
+const FIRST_LAYER: u8 = 31;
+const LAST_LAYER: u8 = 16;
+
+// Effect cameras draw in list order, after everything that renders into the main texture (the cabin, at 10, comes last)
+const FIRST_ORDER: isize = 11;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EffectKind {
+	Test,
+	Flip,
+	Dither,
+	Feedback,
+	Limiter,
+	JpegEncode,
+	JpegDecode,
+}
+
+// Runs $body with $effect standing in for the effect type of $kind
+macro_rules! with_effect {
+	($kind:expr, $effect:ident => $body:expr) => {
+		match $kind {
+			EffectKind::Test => {type $effect = test::Effect; $body},
+			EffectKind::Flip => {type $effect = flip::Effect; $body},
+			EffectKind::Dither => {type $effect = dither::Effect; $body},
+			EffectKind::Feedback => {type $effect = feedback::Effect; $body},
+			EffectKind::Limiter => {type $effect = limiter::Effect; $body},
+			EffectKind::JpegEncode => {type $effect = jpeg::Encode; $body},
+			EffectKind::JpegDecode => {type $effect = jpeg::Decode; $body},
+		}
+	};
+}
+
+impl EffectKind {
+	pub fn n_slots(self) -> usize {
+		with_effect!(self, E => <E as PostProcessingEffect>::MaterialType::n_slots())
+	}
+
+	fn spawn(self, world: &mut World, camera_layer: u8, camera_order: isize, output: EffectOutput) -> Result<Entity, PostProcessingError> {
+		with_effect!(self, E => spawn_effect::<E>(world, camera_layer, camera_order, output))
+	}
+
+	fn link_effect(self, world: &mut World, source: Entity, destination: Entity, slot: usize) -> Result<(), PostProcessingError> {
+		with_effect!(self, E => link_effect::<E>(world, source, destination, slot))
+	}
+
+	fn link_texture(self, world: &mut World, source: Handle<Image>, destination: Entity, slot: usize) -> Result<(), PostProcessingError> {
+		with_effect!(self, E => link_texture::<E>(world, source, destination, slot))
+	}
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub enum GraphInput {
+	// What the gameplay cameras rendered this frame
+	Main,
+	// Output of another node, reading one further down the list gets last frame's picture
+	Node(String),
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct GraphNode {
+	pub name: String,
+	pub effect: EffectKind,
+	pub output: EffectOutput,
+	// By slot, so the first input goes in slot 0
+	#[serde(default)]
+	pub inputs: Vec<GraphInput>,
+	// Left out unless safe mode is on, whatever reads it reads its first input instead
+	#[serde(default)]
+	pub safe_mode_only: bool,
+}
+
+// The whole post-processing chain, loaded from disk so it can be tweaked while the game runs
+#[derive(Deserialize, TypeUuid, Clone, Debug)]
+#[uuid = "5e8a1c27-94d3-4b6f-a0e2-7c3b9d1f4a68"]
+pub struct PostProcessingGraph {
+	pub nodes: Vec<GraphNode>,
+}
+
+impl PostProcessingGraph {
+	fn node(&self, name: &str) -> Option<&GraphNode> {
+		self.nodes.iter().find(|node| node.name == name)
+	}
+
+	// Follows safe mode only nodes back through their first inputs, None if that never leads anywhere else
+	fn bypass(&self, input: &GraphInput) -> Option<GraphInput> {
+		let mut input = input.clone();
+		for _ in 0..=self.nodes.len() {
+			let GraphInput::Node(name) = &input else {
+				return Some(input);
+			};
+			match self.node(name) {
+				Some(node) if node.safe_mode_only => input = node.inputs.first()?.clone(),
+				_ => return Some(input),
+			}
+		}
+		None
+	}
+
+	// The graph as it runs outside safe mode
+	pub fn without_safe_mode_nodes(&self) -> PostProcessingGraph {
+		PostProcessingGraph {
+			nodes: self.nodes.iter()
+				.filter(|node| !node.safe_mode_only)
+				.map(|node| GraphNode {
+					inputs: node.inputs.iter().map(|input| self.bypass(input).expect("Bypasses were validated")).collect(),
+					..node.clone()
+				})
+				.collect(),
+		}
+	}
+
+	// Everything that can be checked without a world to build into
+	fn validate(&self) -> Result<(), PostProcessingGraphError> {
+		let mut names = HashSet::default();
+		for node in self.nodes.iter() {
+			if !names.insert(node.name.as_str()) {
+				return Err(PostProcessingGraphError::DuplicateNode(node.name.clone()));
+			}
+		}
+		if self.nodes.len() > (FIRST_LAYER - LAST_LAYER + 1) as usize {
+			return Err(PostProcessingGraphError::TooManyNodes(self.nodes.len()));
+		}
+		if !self.nodes.iter().any(|node| node.output == EffectOutput::Window) {
+			return Err(PostProcessingGraphError::NoWindowOutput);
+		}
+
+		for node in self.nodes.iter() {
+			let slots = node.effect.n_slots();
+			if node.inputs.len() > slots {
+				return Err(PostProcessingGraphError::TooManyInputs {node: node.name.clone(), inputs: node.inputs.len(), slots});
+			}
+			for input in node.inputs.iter() {
+				let GraphInput::Node(source) = input else {
+					continue;
+				};
+				match self.node(source) {
+					None => return Err(PostProcessingGraphError::DanglingInput {node: node.name.clone(), input: source.clone()}),
+					Some(source) if source.output == EffectOutput::Window => {
+						return Err(PostProcessingGraphError::WindowInput {node: node.name.clone(), input: source.name.clone()});
+					},
+					Some(source) if source.name == node.name => {
+						return Err(PostProcessingGraphError::ReadsItself(node.name.clone()));
+					},
+					Some(_) => {},
+				}
+			}
+
+			if node.safe_mode_only {
+				if node.output == EffectOutput::Window {
+					return Err(PostProcessingGraphError::SafeModeWindow(node.name.clone()));
+				}
+				if self.bypass(&GraphInput::Node(node.name.clone())).is_none() {
+					return Err(PostProcessingGraphError::CannotBypass(node.name.clone()));
+				}
+			}
+		}
+		Ok(())
+	}
+
+	// Not wrong as such, but most likely a mistake in the file
+	fn report_unused(&self) {
+		for node in self.nodes.iter() {
+			if node.inputs.len() < node.effect.n_slots() {
+				warn!("Post-processing node {} only fills {} of its {} inputs", node.name, node.inputs.len(), node.effect.n_slots());
+			}
+			let read = self.nodes.iter().any(|other| other.inputs.contains(&GraphInput::Node(node.name.clone())));
+			if !read && node.output != EffectOutput::Window {
+				warn!("Post-processing node {} isn't read by anything", node.name);
+			}
+		}
+	}
+
+	// Spawns every node and wires them up, hands back the effect entities so they can be torn down again
+	pub fn build(&self, world: &mut World) -> Result<Vec<Entity>, PostProcessingGraphError> {
+		self.validate()?;
+		self.report_unused();
+
+		let mut entities = Vec::with_capacity(self.nodes.len());
+		let built = self.build_into(world, &mut entities);
+		// Don't leave half a chain behind
+		if built.is_err() {
+			despawn_effects(world, &entities);
+		}
+		built.map(|_| entities)
+	}
+
+	fn build_into(&self, world: &mut World, entities: &mut Vec<Entity>) -> Result<(), PostProcessingGraphError> {
+		let failed = |node: &GraphNode| {
+			let node = node.name.clone();
+			move |error| PostProcessingGraphError::Effect {node, error}
+		};
+
+		for (i, node) in self.nodes.iter().enumerate() {
+			entities.push(node.effect.spawn(world, FIRST_LAYER - i as u8, FIRST_ORDER + i as isize, node.output).map_err(failed(node))?);
+		}
+
+		let main_texture = world.get_resource::<MainRenderTexture>()
+			.ok_or(PostProcessingError::MissingResource("MainRenderTexture"))
+			.map_err(failed(&self.nodes[0]))?
+			.texture.clone();
+		for (node, &entity) in self.nodes.iter().zip(entities.iter()) {
+			for (slot, input) in node.inputs.iter().enumerate() {
+				match input {
+					GraphInput::Main => node.effect.link_texture(world, main_texture.clone(), entity, slot),
+					GraphInput::Node(source) => {
+						let source = self.nodes.iter().position(|other| other.name == *source).expect("Inputs were validated");
+						node.effect.link_effect(world, entities[source], entity, slot)
+					},
+				}.map_err(failed(node))?;
+			}
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug)]
+pub enum PostProcessingGraphError {
+	Parse(ron::error::SpannedError),
+	DuplicateNode(String),
+	TooManyNodes(usize),
+	NoWindowOutput,
+	TooManyInputs {node: String, inputs: usize, slots: usize},
+	DanglingInput {node: String, input: String},
+	WindowInput {node: String, input: String},
+	ReadsItself(String),
+	SafeModeWindow(String),
+	CannotBypass(String),
+	Effect {node: String, error: PostProcessingError},
+}
+
+impl Display for PostProcessingGraphError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			PostProcessingGraphError::Parse(error) => write!(f, "malformed post-processing graph: {}", error),
+			PostProcessingGraphError::DuplicateNode(name) => write!(f, "more than one node is called {}", name),
+			PostProcessingGraphError::TooManyNodes(count) => write!(f, "{} nodes, but only {} render layers to go around", count, FIRST_LAYER - LAST_LAYER + 1),
+			PostProcessingGraphError::NoWindowOutput => write!(f, "no node outputs to the window"),
+			PostProcessingGraphError::TooManyInputs {node, inputs, slots} => write!(f, "node {} has {} inputs but only {} slots", node, inputs, slots),
+			PostProcessingGraphError::DanglingInput {node, input} => write!(f, "node {} reads from {}, which doesn't exist", node, input),
+			PostProcessingGraphError::WindowInput {node, input} => write!(f, "node {} reads from {}, which outputs to the window", node, input),
+			PostProcessingGraphError::ReadsItself(node) => write!(f, "node {} reads from itself", node),
+			PostProcessingGraphError::SafeModeWindow(node) => write!(f, "node {} outputs to the window, so it can't be left out of safe mode", node),
+			PostProcessingGraphError::CannotBypass(node) => write!(f, "node {} is safe mode only, but has no input to stand in for it", node),
+			PostProcessingGraphError::Effect {node, error} => write!(f, "node {}: {}", node, error),
+		}
+	}
+}
+
+impl std::error::Error for PostProcessingGraphError {}
+
+#[derive(Default)]
+pub struct PostProcessingGraphLoader;
+
+impl AssetLoader for PostProcessingGraphLoader {
+	fn load<'a>(
+		&'a self,
+		bytes: &'a [u8],
+		load_context: &'a mut LoadContext,
+	) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+		Box::pin(async move {
+			let graph = ron::de::from_bytes::<PostProcessingGraph>(bytes).map_err(PostProcessingGraphError::Parse)?;
+			graph.validate()?;
+			load_context.set_default_asset(LoadedAsset::new(graph));
+			Ok(())
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["graph.ron"]
+	}
+}
+
+// Despawns effects along with the cameras that render them
+pub fn despawn_effects(world: &mut World, effects: &[Entity]) {
+	for &effect in effects {
+		if let Some(camera) = world.get::<EffectAssociatedCameraID>(effect).map(|camera| camera.0) {
+			world.despawn(camera);
+		}
+		world.despawn(effect);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn node(name: &str, output: EffectOutput, inputs: Vec<GraphInput>, safe_mode_only: bool) -> GraphNode {
+		GraphNode {name: name.into(), effect: EffectKind::Flip, output, inputs, safe_mode_only}
+	}
+
+	#[test]
+	fn outside_safe_mode_the_screen_reads_past_the_limiter() {
+		let graph = crate::vfx::built_in_graph();
+		assert!(graph.validate().is_ok());
+
+		let graph = graph.without_safe_mode_nodes();
+		assert!(graph.validate().is_ok());
+		assert!(graph.node("limiter").is_none() && graph.node("history").is_none());
+		assert_eq!(graph.node("screen").unwrap().inputs, vec![GraphInput::Node("feedback".into())]);
+	}
+
+	#[test]
+	fn safe_mode_only_nodes_need_somewhere_to_fall_back_to() {
+		let screen = node("screen", EffectOutput::Window, vec![GraphInput::Node("a".into())], false);
+
+		let window = PostProcessingGraph {nodes: vec![node("screen", EffectOutput::Window, vec![GraphInput::Main], true)]};
+		assert!(matches!(window.validate(), Err(PostProcessingGraphError::SafeModeWindow(_))));
+
+		let empty = PostProcessingGraph {nodes: vec![
+			node("a", EffectOutput::Texture {scale: 1.0}, vec![], true),
+			screen.clone(),
+		]};
+		assert!(matches!(empty.validate(), Err(PostProcessingGraphError::CannotBypass(_))));
+
+		let looped = PostProcessingGraph {nodes: vec![
+			node("a", EffectOutput::Texture {scale: 1.0}, vec![GraphInput::Node("b".into())], true),
+			node("b", EffectOutput::Texture {scale: 1.0}, vec![GraphInput::Node("a".into())], true),
+			screen,
+		]};
+		assert!(matches!(looped.validate(), Err(PostProcessingGraphError::CannotBypass(_))));
+	}
+}
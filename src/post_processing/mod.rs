@@ -1,4 +1,5 @@
 pub mod effects;
+pub mod graph;
 pub mod setup;
 
 use std::marker::PhantomData;
@@ -10,7 +11,7 @@ use bevy::{
 	render::render_resource::AsBindGroup
 };
 
-pub use setup::{VFXPlugin, spawn_effect, link_effect, update_effect};
+pub use setup::{VFXPlugin, PostProcessingError, update_effect};
 
 use crate::GameState;
 
@@ -33,7 +34,7 @@ where <T::MaterialType as AsBindGroup>::Data: PartialEq + Eq + Hash + Clone {
 pub trait PostProcessingEffectMaterial : Material2d {
 	fn new() -> Self;
 	fn n_slots() -> usize;
-	fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(),()>;
+	fn set_slot(&mut self, slot: usize, texture: Handle<Image>) -> Result<(), PostProcessingError>;
 }
 
 pub trait PostProcessingEffect : Component + Default {
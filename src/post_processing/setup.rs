@@ -3,101 +3,158 @@
 //! This example is useful to implement your own post-processing effect such as
 //! edge detection, blur, pixelization, vignette... and countless others.
 
+use std::fmt::Display;
+
 use bevy::{
 	prelude::*,
 	render::{view::RenderLayers, render_resource::{Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages},
-	texture::BevyDefault, camera::{RenderTarget, ScalingMode}}, sprite::MaterialMesh2dBundle,
+	texture::BevyDefault, camera::{RenderTarget, ScalingMode}},
+	sprite::{MaterialMesh2dBundle, Mesh2dHandle}, window::PrimaryWindow,
 };
 
-use super::{PostProcessingEffect, PostProcessingEffectMaterial, EffectAssociatedCameraID};
+use serde::Deserialize;
+
+use crate::resources::MainRenderTexture;
+
+use super::{PostProcessingEffect, PostProcessingEffectMaterial, EffectAssociatedCameraID, graph::{PostProcessingGraph, PostProcessingGraphLoader}};
 
 #[derive(Debug, Default)]
 pub struct VFXPlugin {}
 
 impl Plugin for VFXPlugin {
-	fn build(&self, _app: &mut App) {
-		
+	fn build(&self, app: &mut App) {
+		app
+		.add_asset::<PostProcessingGraph>()
+		.init_asset_loader::<PostProcessingGraphLoader>()
+		.add_system(resize_effects);
 	}
 }
 
-#[derive(Clone)]
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
 pub enum EffectOutput {
-	Window {
-		output_window: Window
-	},
+	Window,
 	Texture {
-		width: u32,
-		height: u32,
+		// Relative to the main render texture
+		scale: f32,
+	}
+}
+
+impl EffectOutput {
+	fn size(&self, main: UVec2, window: UVec2) -> UVec2 {
+		match *self {
+			EffectOutput::Window => window,
+			EffectOutput::Texture {scale} => (main.as_vec2() * scale).round().as_uvec2().max(UVec2::ONE),
+		}
 	}
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum PostProcessingError {
+	UnknownSlot {slot: usize, slots: usize},
+	// The source renders straight to the window, there's no image to read
+	NoTextureOutput(Entity),
+	// The entity isn't an effect of the type it was used as
+	NotAnEffect(Entity),
+	MissingMaterialAsset(Entity),
+	// No Assets for the material type, which adding the effect's EffectPlugin (through its Material2dPlugin) would have registered
+	UnregisteredMaterial(&'static str),
+	MissingResource(&'static str),
+}
+
+impl Display for PostProcessingError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			PostProcessingError::UnknownSlot {slot, slots} => write!(f, "slot {} doesn't exist, the effect has {}", slot, slots),
+			PostProcessingError::NoTextureOutput(entity) => write!(f, "effect {:?} outputs to the window, not a texture", entity),
+			PostProcessingError::NotAnEffect(entity) => write!(f, "{:?} isn't an effect of that type", entity),
+			PostProcessingError::MissingMaterialAsset(entity) => write!(f, "the material of effect {:?} doesn't exist", entity),
+			PostProcessingError::UnregisteredMaterial(material) => write!(f, "there are no Assets<{}>, was its EffectPlugin added?", material),
+			PostProcessingError::MissingResource(resource) => write!(f, "{} is missing from the world", resource),
+		}
+	}
+}
+
+impl std::error::Error for PostProcessingError {}
+
+// What an effect renders into, kept around so it can be resized later
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub struct EffectTarget(pub EffectOutput);
+
+// Blank image that cameras can render to
+pub fn render_target_image(size: UVec2) -> Image {
+	let size = Extent3d {width: size.x, height: size.y, ..default()};
+	let mut image = Image {
+		texture_descriptor: TextureDescriptor {
+			label: None,
+			size,
+			dimension: TextureDimension::D2,
+			format: TextureFormat::bevy_default(),
+			mip_level_count: 1,
+			sample_count: 1,
+			usage: TextureUsages::TEXTURE_BINDING
+				| TextureUsages::COPY_DST
+				| TextureUsages::RENDER_ATTACHMENT,
+			view_formats: &[],
+		},
+		..default()
+	};
+	// fill image.data with zeroes
+	image.resize(size);
+	image
+}
+
+fn window_size(window: Option<&Window>, main: UVec2) -> UVec2 {
+	window.map(|window| UVec2::new(window.physical_width(), window.physical_height()))
+		.filter(|size| size.x > 0 && size.y > 0)
+		.unwrap_or(main)
+}
+
 pub fn spawn_effect<Effect: PostProcessingEffect>(
 	world: &mut World,
 	camera_layer: u8,
+	camera_order: isize,
 	output: EffectOutput,
-) -> Entity {
-	let size = match output.clone() {
-		EffectOutput::Window {output_window: _} => Extent3d {
-			width: 1280,
-			height: 720,
-			..default()
-		},
-		EffectOutput::Texture {width, height} => Extent3d {
-			width, height, ..default()
-		}
+) -> Result<Entity, PostProcessingError> {
+	let main = world.get_resource::<MainRenderTexture>().ok_or(PostProcessingError::MissingResource("MainRenderTexture"))?.size();
+	let window = world.query_filtered::<&Window, With<PrimaryWindow>>().get_single(world).ok().cloned();
+	let size = output.size(main, window_size(window.as_ref(), main));
+
+	// This material has the texture that has been rendered.
+	// Checked first so an unregistered material doesn't leave an orphaned image and quad behind.
+	let material_handle = {
+		let mut post_processing_materials = world.get_resource_mut::<Assets<Effect::MaterialType>>()
+			.ok_or(PostProcessingError::UnregisteredMaterial(std::any::type_name::<Effect::MaterialType>()))?;
+		post_processing_materials.add(Effect::MaterialType::new())	
 	};
 
 	let (image_handle, render_target) = match output {
-	EffectOutput::Window {output_window: _} => (None, RenderTarget::Window(bevy::window::WindowRef::Primary)),
-	EffectOutput::Texture {width: _, height: _} => {
-		// This is the texture that will be rendered to.
-		let mut image = Image {
-			texture_descriptor: TextureDescriptor {
-				label: None,
-				size: size,
-				dimension: TextureDimension::D2,
-				format: TextureFormat::bevy_default(),
-				mip_level_count: 1,
-				sample_count: 1,
-				usage: TextureUsages::TEXTURE_BINDING
-					| TextureUsages::COPY_DST
-					| TextureUsages::RENDER_ATTACHMENT,
-				view_formats: &[],
-			},
-			..default()
-		};
-
-		// fill image.data with zeroes
-		image.resize(size);
-		let image_handle = world.resource_mut::<Assets<Image>>().add(image);
-		(Some(image_handle.clone()), RenderTarget::Image(image_handle))
-	}};
+		EffectOutput::Window => (None, RenderTarget::Window(bevy::window::WindowRef::Primary)),
+		EffectOutput::Texture {..} => {
+			// This is the texture that will be rendered to.
+			let mut images = world.get_resource_mut::<Assets<Image>>().ok_or(PostProcessingError::MissingResource("Assets<Image>"))?;
+			let image_handle = images.add(render_target_image(size));
+			(Some(image_handle.clone()), RenderTarget::Image(image_handle))
+		}
+	};
 
 	// This specifies the layer used for the post processing camera, which will be attached to the post processing camera and 2d quad.
 	let post_processing_pass_layer = RenderLayers::layer(camera_layer);
 
 	let quad_handle = {
-		let mut meshes = world.get_resource_mut::<Assets<Mesh>>().unwrap();
-		meshes.add(Mesh::from(shape::Quad::new(Vec2::new(size.width as f32, size.height as f32))))
-	};
-
-	// This material has the texture that has been rendered.
-	let material_handle = {
-		let mut post_processing_materials = world.get_resource_mut::<Assets<Effect::MaterialType>>().expect("There should be a Material2DPlugin for the shader material");
-		post_processing_materials.add(Effect::MaterialType::new())	
+		let mut meshes = world.get_resource_mut::<Assets<Mesh>>().ok_or(PostProcessingError::MissingResource("Assets<Mesh>"))?;
+		meshes.add(Mesh::from(shape::Quad::new(size.as_vec2())))
 	};
 
 	// The post-processing pass camera.
 	let camera_id = world.spawn((
 		Camera2dBundle {
 			camera: Camera {
-				// renders after the first main camera which has default value: 0.
-				order: 1,
+				order: camera_order,
 				target: render_target,
 				..default()
 			},
 			projection: OrthographicProjection {
-				scaling_mode: ScalingMode::Fixed {width: size.width as f32, height: size.height as f32},
+				scaling_mode: ScalingMode::Fixed {width: size.x as f32, height: size.y as f32},
 				..Default::default()
 			},
 			..Camera2dBundle::default()
@@ -119,14 +176,15 @@ pub fn spawn_effect<Effect: PostProcessingEffect>(
 			..default()
 		},
 		post_processing_pass_layer,
-		EffectAssociatedCameraID(camera_id)
+		EffectAssociatedCameraID(camera_id),
+		EffectTarget(output),
 	));
 
 	if let Some(handle) = image_handle {
 		effect_entity.insert(handle);
 	}
 	
-	effect_entity.id()
+	Ok(effect_entity.id())
 }
 
 pub fn link_effect<Effect: PostProcessingEffect>(
@@ -134,12 +192,13 @@ pub fn link_effect<Effect: PostProcessingEffect>(
 	source_effect: Entity,
 	destination_effect: Entity,
 	destination_slot: usize,
-) {
-	let source_image = world.get::<Handle<Image>>(source_effect).expect("Effect should have an image (unless it's outputting to the window)").clone();
-	let destination_material_handle = world.get::<Handle<Effect::MaterialType>>(destination_effect).expect("Effect entity has a corresponding Material component").clone();
-
-	let mut materials = world.resource_mut::<Assets<Effect::MaterialType>>();
-	materials.get_mut(&destination_material_handle).expect("Material should exist").set_slot(destination_slot, source_image).expect("Something went wrong idk");
+) -> Result<(), PostProcessingError> {
+	let source = world.get_entity(source_effect).ok_or(PostProcessingError::NotAnEffect(source_effect))?;
+	if !source.contains::<EffectTarget>() {
+		return Err(PostProcessingError::NotAnEffect(source_effect));
+	}
+	let source_image = source.get::<Handle<Image>>().ok_or(PostProcessingError::NoTextureOutput(source_effect))?.clone();
+	link_texture::<Effect>(world, source_image, destination_effect, destination_slot)
 }
 
 pub fn link_texture<Effect: PostProcessingEffect>(
@@ -147,11 +206,17 @@ pub fn link_texture<Effect: PostProcessingEffect>(
 	source_image: Handle<Image>,
 	destination_effect: Entity,
 	destination_slot: usize,
-) {
-	let destination_material_handle = world.get::<Handle<Effect::MaterialType>>(destination_effect).expect("Effect entity has a corresponding Material component").clone();
+) -> Result<(), PostProcessingError> {
+	let destination_material_handle = world.get_entity(destination_effect)
+		.and_then(|entity| entity.get::<Handle<Effect::MaterialType>>())
+		.ok_or(PostProcessingError::NotAnEffect(destination_effect))?
+		.clone();
 
-	let mut materials = world.resource_mut::<Assets<Effect::MaterialType>>();
-	materials.get_mut(&destination_material_handle).expect("Material should exist").set_slot(destination_slot, source_image).expect("Something went wrong idk");
+	let mut materials = world.get_resource_mut::<Assets<Effect::MaterialType>>()
+		.ok_or(PostProcessingError::UnregisteredMaterial(std::any::type_name::<Effect::MaterialType>()))?;
+	materials.get_mut(&destination_material_handle)
+		.ok_or(PostProcessingError::MissingMaterialAsset(destination_effect))?
+		.set_slot(destination_slot, source_image)
 }
 
 pub fn update_effect<Effect: PostProcessingEffect>(
@@ -165,3 +230,113 @@ pub fn update_effect<Effect: PostProcessingEffect>(
 		}
 	}
 }
+
+// Follows the main render texture, targets get reallocated and quads and projections stretched to match
+fn resize_effects(
+	main: Res<MainRenderTexture>,
+	windows: Query<&Window, With<PrimaryWindow>>,
+	effects: Query<(&EffectTarget, &Mesh2dHandle, Option<&Handle<Image>>, &EffectAssociatedCameraID)>,
+	mut cameras: Query<&mut OrthographicProjection>,
+	mut images: ResMut<Assets<Image>>,
+	mut meshes: ResMut<Assets<Mesh>>,
+) {
+	if !main.is_changed() {
+		return;
+	}
+	let main_size = main.size();
+	let window = window_size(windows.get_single().ok(), main_size);
+
+	for (target, quad, image, camera) in effects.iter() {
+		let size = target.0.size(main_size, window);
+		if let Some(image) = image.and_then(|image| images.get_mut(image)) {
+			if image.size().as_uvec2() != size {
+				image.resize(Extent3d {width: size.x, height: size.y, ..default()});
+			}
+		}
+		if let Some(mesh) = meshes.get_mut(&quad.0) {
+			*mesh = Mesh::from(shape::Quad::new(size.as_vec2()));
+		}
+		if let Ok(mut projection) = cameras.get_mut(camera.0) {
+			projection.scaling_mode = ScalingMode::Fixed {width: size.x as f32, height: size.y as f32};
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::effects::flip;
+
+	fn effect_world(register_material: bool) -> World {
+		let mut app = App::new();
+		app
+		.add_plugins(MinimalPlugins)
+		.add_plugin(AssetPlugin::default())
+		.add_asset::<Image>()
+		.add_asset::<Mesh>();
+		if register_material {
+			app.add_asset::<flip::Material>();
+		}
+		app.init_resource::<MainRenderTexture>();
+		std::mem::take(&mut app.world)
+	}
+
+	fn texture_output() -> EffectOutput {
+		EffectOutput::Texture {scale: 1.0}
+	}
+
+	#[test]
+	fn linking_to_an_unknown_slot_fails() {
+		let mut world = effect_world(true);
+		let effect = spawn_effect::<flip::Effect>(&mut world, 1, 0, texture_output()).unwrap();
+		let slots = flip::Material::n_slots();
+
+		let result = link_texture::<flip::Effect>(&mut world, Handle::default(), effect, slots);
+		assert_eq!(result, Err(PostProcessingError::UnknownSlot {slot: slots, slots}));
+	}
+
+	#[test]
+	fn a_window_output_has_no_texture_to_link() {
+		let mut world = effect_world(true);
+		let screen = spawn_effect::<flip::Effect>(&mut world, 1, 0, EffectOutput::Window).unwrap();
+		let destination = spawn_effect::<flip::Effect>(&mut world, 2, 1, texture_output()).unwrap();
+
+		let result = link_effect::<flip::Effect>(&mut world, screen, destination, 0);
+		assert_eq!(result, Err(PostProcessingError::NoTextureOutput(screen)));
+	}
+
+	#[test]
+	fn linking_into_a_removed_material_fails() {
+		let mut world = effect_world(true);
+		let source = spawn_effect::<flip::Effect>(&mut world, 1, 0, texture_output()).unwrap();
+		let destination = spawn_effect::<flip::Effect>(&mut world, 2, 1, texture_output()).unwrap();
+
+		let material = world.get::<Handle<flip::Material>>(destination).unwrap().clone();
+		world.resource_mut::<Assets<flip::Material>>().remove(&material);
+
+		let result = link_effect::<flip::Effect>(&mut world, source, destination, 0);
+		assert_eq!(result, Err(PostProcessingError::MissingMaterialAsset(destination)));
+	}
+
+	#[test]
+	fn spawning_an_unregistered_material_creates_nothing() {
+		let mut world = effect_world(false);
+		let images = world.resource::<Assets<Image>>().len();
+
+		let result = spawn_effect::<flip::Effect>(&mut world, 1, 0, texture_output());
+		assert_eq!(result, Err(PostProcessingError::UnregisteredMaterial(std::any::type_name::<flip::Material>())));
+		assert_eq!(world.resource::<Assets<Image>>().len(), images);
+		assert!(world.resource::<Assets<Mesh>>().is_empty());
+		assert_eq!(world.entities().len(), 0);
+	}
+
+	#[test]
+	fn a_plain_entity_is_not_an_effect() {
+		let mut world = effect_world(true);
+		let effect = spawn_effect::<flip::Effect>(&mut world, 1, 0, texture_output()).unwrap();
+		let plain = world.spawn_empty().id();
+
+		assert_eq!(link_effect::<flip::Effect>(&mut world, plain, effect, 0), Err(PostProcessingError::NotAnEffect(plain)));
+		assert_eq!(link_texture::<flip::Effect>(&mut world, Handle::default(), plain, 0), Err(PostProcessingError::NotAnEffect(plain)));
+	}
+}
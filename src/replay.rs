@@ -0,0 +1,460 @@
+use std::{path::{Path, PathBuf}, time::Duration, sync::atomic::{AtomicBool, Ordering}};
+
+use bevy::{prelude::*, app::AppExit, asset::{LoadState, HandleId}, ecs::system::SystemParam, input::InputSystem, time::{TimeSystem, TimeUpdateStrategy}, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{prelude::*, input::{ActionState, Action, update_action_state}, thoughts::data::{ThoughtLibrary, ThoughtLibraryHandle}};
+
+// Everything that came in from outside during one frame, quiet frames can leave most of it out
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RecordedFrame {
+	delta: Duration,
+	#[serde(default, skip_serializing_if = "HashMap::is_empty")]
+	actions: HashMap<Action, f32>,
+	// Mouse motion goes by fixed steps instead of frames, so it's kept apart from the rest
+	#[serde(default, skip_serializing_if = "HashMap::is_empty")]
+	motion: HashMap<Action, f32>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	mouse: Vec<MouseButton>,
+	#[serde(default)]
+	cursor: CursorCabinPosition,
+}
+
+// Where the run ended up, a replay has to end up in the same place
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ReplayOutcome {
+	score: u32,
+	good_progress: f32,
+	bad_progress: f32,
+}
+
+impl ReplayOutcome {
+	fn new(score: &ScoreCounter, progress: &ProgressBar) -> Self {
+		ReplayOutcome {
+			score: score.score,
+			good_progress: progress.good_progress,
+			bad_progress: progress.bad_progress,
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Recording {
+	seed: u64,
+	frames: Vec<RecordedFrame>,
+	#[serde(default)]
+	outcome: Option<ReplayOutcome>,
+}
+
+// Set from `--record <file>` or `--replay <file>` on the command line
+#[derive(Resource, Debug, Clone, Default)]
+pub enum ReplayMode {
+	#[default]
+	Off,
+	Recording {path: PathBuf, recording: Recording},
+	Replaying {
+		path: PathBuf,
+		recording: Recording,
+		frame: usize,
+		// Recorded time up to and including frame, counted from when the clock started
+		elapsed: Duration,
+	},
+}
+
+impl ReplayMode {
+	pub fn from_args() -> Self {
+		let mut args = std::env::args().skip(1);
+		while let Some(arg) = args.next() {
+			if arg != "--record" && arg != "--replay" {
+				continue;
+			}
+			let Some(path) = args.next() else {
+				error!("{} needs a file to go with it", arg);
+				return ReplayMode::Off;
+			};
+			if arg == "--record" {
+				return ReplayMode::Recording {path: path.into(), recording: Recording::default()};
+			}
+			return ReplayMode::replay(&path).unwrap_or_else(|error| {
+				error!("Could not read replay {}: {}", path, error);
+				ReplayMode::Off
+			});
+		}
+		ReplayMode::Off
+	}
+
+	pub fn replay(path: impl AsRef<Path>) -> Result<Self, String> {
+		let path = path.as_ref();
+		let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+		let recording = ron::from_str::<Recording>(&text).map_err(|error| error.to_string())?;
+		let elapsed = recording.frames.first().map_or(Duration::ZERO, |frame| frame.delta);
+		Ok(ReplayMode::Replaying {path: path.into(), recording, frame: 0, elapsed})
+	}
+}
+
+// Set when a replay ends up somewhere other than its recording did, main turns it into the exit code
+static DIVERGED: AtomicBool = AtomicBool::new(false);
+
+pub fn diverged() -> bool {
+	DIVERGED.load(Ordering::Relaxed)
+}
+
+// `--headless` runs without a window or GPU, only useful together with `--replay`
+pub fn headless() -> bool {
+	std::env::args().any(|arg| arg == "--headless")
+}
+
+pub fn is_replaying(mode: Res<ReplayMode>) -> bool {
+	matches!(*mode, ReplayMode::Replaying {..})
+}
+
+fn is_recording(mode: Res<ReplayMode>) -> bool {
+	matches!(*mode, ReplayMode::Recording {..})
+}
+
+// Goes by the command line unless given a mode
+#[derive(Default)]
+pub struct ReplayPlugin {
+	pub mode: Option<ReplayMode>,
+}
+
+impl Plugin for ReplayPlugin {
+	fn build(&self, app: &mut App) {
+		let mode = self.mode.clone().unwrap_or_else(ReplayMode::from_args);
+		if let ReplayMode::Replaying {path, recording, ..} = &mode {
+			info!("Replaying {} ({} frames)", path.display(), recording.frames.len());
+			// The recorded seed wins over whatever was rolled or passed in
+			app.insert_resource(GameRng::from_seed(recording.seed));
+		}
+
+		app
+		.insert_resource(mode)
+		.add_startup_system(start_recording.run_if(is_recording))
+		.add_system(record_frame.in_base_set(CoreSet::Last).run_if(is_recording))
+		.add_system(save_recording.in_base_set(CoreSet::Last).after(record_frame).run_if(is_recording))
+		.add_system(replay_time.in_base_set(CoreSet::First).before(TimeSystem).run_if(is_replaying))
+		.add_system(replay_inputs.in_base_set(CoreSet::PreUpdate).after(InputSystem).after(update_action_state).run_if(is_replaying))
+		.add_system(advance_replay.in_base_set(CoreSet::Last).run_if(is_replaying))
+		;
+	}
+}
+
+fn start_recording(mut mode: ResMut<ReplayMode>, rng: Res<GameRng>) {
+	if let ReplayMode::Recording {path, recording} = mode.as_mut() {
+		info!("Recording input to {}", path.display());
+		recording.seed = rng.seed();
+	}
+}
+
+fn record_frame(
+	mut mode: ResMut<ReplayMode>,
+	time: Res<Time>,
+	actions: Res<ActionState>,
+	mouse: Res<Input<MouseButton>>,
+	cursor: Res<CursorCabinPosition>,
+) {
+	if let ReplayMode::Recording {recording, ..} = mode.as_mut() {
+		recording.frames.push(RecordedFrame {
+			delta: time.delta(),
+			actions: actions.values(),
+			motion: actions.new_motion(),
+			mouse: mouse.get_pressed().copied().collect(),
+			cursor: cursor.clone(),
+		});
+	}
+}
+
+fn save_recording(
+	mut mode: ResMut<ReplayMode>,
+	mut exit: EventReader<AppExit>,
+	score: Option<Res<ScoreCounter>>,
+	progress: Res<ProgressBar>,
+) {
+	if exit.iter().next().is_none() {
+		return;
+	}
+	let ReplayMode::Recording {path, recording} = mode.as_mut() else {
+		return;
+	};
+	recording.outcome = score.map(|score| ReplayOutcome::new(&score, &progress));
+	let saved = ron::ser::to_string(recording)
+		.map_err(|error| error.to_string())
+		.and_then(|text| std::fs::write(&path, text).map_err(|error| error.to_string()));
+	match saved {
+		Ok(()) => info!("Saved {} recorded frames to {}", recording.frames.len(), path.display()),
+		Err(error) => error!("Could not save recording to {}: {}", path.display(), error),
+	}
+}
+
+// Steps the clock by exactly what the recorded frame took, which keeps the fixed timestep in lockstep
+// ManualDuration would add however long the frame really took on top, so this sets the instant instead
+// Going from the clock's own startup keeps elapsed_seconds the same as it was live too
+fn replay_time(mode: Res<ReplayMode>, time: Res<Time>, mut strategy: ResMut<TimeUpdateStrategy>) {
+	if let ReplayMode::Replaying {elapsed, ..} = mode.as_ref() {
+		*strategy = TimeUpdateStrategy::ManualInstant(time.startup() + *elapsed);
+	}
+}
+
+// Puts the recorded frame where the live devices would have, so the game code can't tell the difference
+fn replay_inputs(
+	mode: Res<ReplayMode>,
+	mut actions: ResMut<ActionState>,
+	mut mouse: ResMut<Input<MouseButton>>,
+	mut cursor: ResMut<CursorCabinPosition>,
+) {
+	let ReplayMode::Replaying {recording, frame, ..} = mode.as_ref() else {
+		return;
+	};
+	let Some(recorded) = recording.frames.get(*frame) else {
+		return;
+	};
+
+	actions.overwrite(&recorded.actions);
+	actions.add_motion(recorded.motion.clone());
+
+	// Rebuild last frame's buttons first so just_pressed and just_released come out the same as they did live
+	let previous = frame.checked_sub(1)
+		.and_then(|previous| recording.frames.get(previous))
+		.map(|previous| previous.mouse.as_slice())
+		.unwrap_or(&[]);
+	mouse.reset_all();
+	for button in previous.iter() {
+		mouse.press(*button);
+	}
+	mouse.clear();
+	for button in previous.iter().filter(|button| !recorded.mouse.contains(button)) {
+		mouse.release(*button);
+	}
+	for button in recorded.mouse.iter() {
+		mouse.press(*button);
+	}
+
+	*cursor = recorded.cursor.clone();
+}
+
+// Live, everything has loaded long before the player gets past the boot slides
+// A replay runs fast enough to beat the loading, so it holds its first frame until the assets are in
+#[derive(SystemParam)]
+struct LoadedAssets<'w, 's> {
+	asset_server: Res<'w, AssetServer>,
+	thoughts: Res<'w, ThoughtLibrary>,
+	thoughts_handle: Option<Res<'w, ThoughtLibraryHandle>>,
+	images: Res<'w, Assets<Image>>,
+	scenes: Res<'w, Assets<Scene>>,
+	image_handles: Query<'w, 's, &'static Handle<Image>>,
+	scene_handles: Query<'w, 's, &'static Handle<Scene>>,
+}
+
+impl LoadedAssets<'_, '_> {
+	fn ready(&self) -> bool {
+		// Anything that will never arrive doesn't get waited on
+		let failed = |handle: HandleId| self.asset_server.get_load_state(handle) == LoadState::Failed;
+		let thoughts_failed = self.thoughts_handle.as_ref().is_some_and(|handle| failed(handle.0.id()));
+		(!self.thoughts.is_empty() || thoughts_failed)
+			&& self.image_handles.iter().all(|handle| self.images.contains(handle) || failed(handle.id()))
+			&& self.scene_handles.iter().all(|handle| self.scenes.contains(handle) || failed(handle.id()))
+	}
+}
+
+fn advance_replay(
+	mut mode: ResMut<ReplayMode>,
+	assets: LoadedAssets,
+	score: Option<Res<ScoreCounter>>,
+	progress: Res<ProgressBar>,
+	mut rng: ResMut<GameRng>,
+	mut exit: EventWriter<AppExit>,
+) {
+	let ReplayMode::Replaying {path, recording, frame, elapsed, ..} = mode.as_mut() else {
+		return;
+	};
+	if *frame == 0 {
+		if !assets.ready() {
+			return;
+		}
+		// However many frames were spent waiting, the recorded run starts from a fresh seed
+		*rng = GameRng::from_seed(recording.seed);
+	}
+	*frame += 1;
+	if let Some(recorded) = recording.frames.get(*frame) {
+		*elapsed += recorded.delta;
+		return;
+	}
+
+	let outcome = score.map(|score| ReplayOutcome::new(&score, &progress));
+	match &recording.outcome {
+		Some(expected) if outcome.as_ref() != Some(expected) => {
+			error!("Replay of {} diverged: expected {:?}, got {:?}", path.display(), expected, outcome);
+			DIVERGED.store(true, Ordering::Relaxed);
+		},
+		Some(_) => info!("Replay of {} matched the recorded outcome", path.display()),
+		None => info!("Replay of {} finished with {:?}, nothing recorded to compare to", path.display(), outcome),
+	}
+	exit.send(AppExit);
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Instant;
+
+	use bevy::{ecs::{event::ManualEventReader, system::SystemState}, time::TimeUpdateStrategy};
+
+	use super::*;
+	use crate::{cabin::LooseCabinThought, GameState};
+
+	// Boots past the slides, flies around picking up thoughts and merges twice,
+	// once by clicking the merge button and once with the Merge key
+	const RECORDING: &str = "tests/replays/boot_and_merge.replay.ron";
+
+	// Only there so a replay that never finishes fails instead of hanging the test run
+	const GIVE_UP_AFTER: Duration = Duration::from_secs(300);
+
+	#[test]
+	fn headless_replay_reaches_the_recorded_outcome() {
+		let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(RECORDING);
+		let mode = ReplayMode::replay(&path).expect("The recording should parse");
+		let ReplayMode::Replaying {recording, ..} = &mode else {
+			unreachable!();
+		};
+		let expected = recording.outcome.clone().expect("The recording should have an outcome to check against");
+		// The cabin has to get a workout, not just the flying
+		assert!(recording.frames.iter().any(|frame| frame.mouse.contains(&MouseButton::Left)), "The recording never clicks");
+		assert!(recording.frames.iter().any(|frame| frame.actions.contains_key(&Action::Merge)), "The recording never presses Merge");
+		assert!(recording.frames.windows(2).any(|frames| frames[0].cursor != frames[1].cursor), "The cursor never moves");
+
+		let mut app = crate::game_app(true, ReplayPlugin {mode: Some(mode)});
+		let mut merges = ManualEventReader::<ThoughtCutsceneEndEvent>::default();
+		let mut merged = 0;
+		let started = Instant::now();
+		while app.world.resource::<Events<AppExit>>().is_empty() {
+			assert!(started.elapsed() < GIVE_UP_AFTER, "The replay never finished");
+			app.update();
+			merged += merges.iter(app.world.resource::<Events<ThoughtCutsceneEndEvent>>()).count();
+		}
+
+		assert!(!diverged(), "The replay should end up where the recording did");
+		assert_eq!(merged, 2, "Both merges should play out again");
+		let outcome = ReplayOutcome::new(app.world.resource::<ScoreCounter>(), app.world.resource::<ProgressBar>());
+		assert_eq!(outcome, expected);
+		assert!(outcome.score >= 200000, "Two merges are worth at least 200000, got {}", outcome.score);
+	}
+
+	const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+	const MERGE_BUTTON: Vec2 = Vec2::new(-7.0, -3.5);
+
+	// A player working through the game on the devices themselves, everything past them is the real thing
+	#[derive(Default)]
+	struct Session {
+		// Frames until whatever is held down gets let go
+		held: u32,
+		next_advance: f32,
+		merging: bool,
+		merges_started: u32,
+		merges_ended: u32,
+		next_pull: f32,
+		done_at: Option<f32>,
+	}
+
+	#[allow(clippy::too_many_arguments)]
+	fn play_session(
+		mut session: Local<Session>,
+		time: Res<Time>,
+		state: Res<State<GameState>>,
+		mut keyboard: ResMut<Input<KeyCode>>,
+		mut mouse: ResMut<Input<MouseButton>>,
+		mut cursor: ResMut<CursorCabinPosition>,
+		loose: Query<(), LooseCabinThought>,
+		directors: Query<(), With<CabinCutsceneDirector>>,
+		mut ended: EventReader<ThoughtCutsceneEndEvent>,
+		mut exit: EventWriter<AppExit>,
+	) {
+		let now = time.elapsed_seconds();
+		assert!(now < 600.0, "The session should be over long before this");
+
+		if session.held > 0 {
+			session.held -= 1;
+			if session.held == 0 {
+				keyboard.release_all();
+				mouse.release_all();
+			}
+		}
+		let idle = session.held == 0;
+
+		if state.0 == GameState::Boot {
+			// The slides only move on after ten seconds
+			if idle && now > 10.5 && now >= session.next_advance {
+				keyboard.press(KeyCode::C);
+				session.held = 3;
+				session.next_advance = now + 1.0;
+			}
+			return;
+		}
+
+		match (directors.is_empty(), session.merging) {
+			(false, false) => {
+				session.merging = true;
+				session.merges_started += 1;
+			},
+			(true, true) => session.merging = false,
+			_ => {},
+		}
+		session.merges_ended += ended.iter().count() as u32;
+
+		let ready = !session.merging && session.merges_started == session.merges_ended && loose.iter().count() >= 3;
+		let mut target = Vec2::new(5.0 * (0.7 * now).sin(), 3.0 * (1.1 * now).sin());
+		if ready && session.merges_ended == 0 {
+			// Over to the button and click it
+			target = MERGE_BUTTON;
+			if idle && cursor.world_position.distance(MERGE_BUTTON) < 0.2 {
+				mouse.press(MouseButton::Left);
+				session.held = 3;
+			}
+		} else if ready && session.merges_ended == 1 && idle {
+			keyboard.press(KeyCode::M);
+			session.held = 3;
+		} else if idle && !session.merging && now >= session.next_pull {
+			// Pulls the collected thoughts towards the cursor for a second
+			mouse.press(MouseButton::Right);
+			session.held = 60;
+			session.next_pull = now + 8.0;
+		}
+
+		let world_position = cursor.world_position.lerp(target, 0.15);
+		cursor.world_position = world_position;
+		cursor.uv_position = world_position / Vec2::new(crate::cabin::CABIN_WIDTH, crate::cabin::CABIN_HEIGHT) + 0.5;
+
+		// A little flying after the second merge, then done
+		if session.merges_ended >= 2 {
+			let done_at = *session.done_at.get_or_insert(now);
+			if now > done_at + 2.0 {
+				exit.send(AppExit);
+			}
+		}
+	}
+
+	// Plays a session through the devices while the game records it, which rewrites RECORDING
+	// `cargo test -- --ignored record_boot_and_merge`
+	#[test]
+	#[ignore]
+	fn record_boot_and_merge() {
+		let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(RECORDING);
+		let mut app = crate::game_app(true, ReplayPlugin {mode: Some(ReplayMode::Recording {path, recording: Recording::default()})});
+		// Flies between the clicks, merging is left to the session
+		let autopilot = AutopilotParameters {idle_seconds: 0.5, merge_at: usize::MAX, ..*app.world.resource::<AutopilotParameters>()};
+		app
+		.insert_resource(autopilot)
+		.add_system(play_session.in_base_set(CoreSet::PreUpdate).after(InputSystem).before(update_action_state));
+
+		let mut assets = SystemState::<LoadedAssets>::new(&mut app.world);
+		// Same as replay_time, ManualDuration would count the real frame time too
+		let mut now = app.world.resource::<Time>().startup();
+		while app.world.resource::<Events<AppExit>>().is_empty() {
+			// Live, everything is in long before the slides can be skipped, so frames take their time until then
+			if app.world.resource::<State<GameState>>().0 == GameState::Boot && !assets.get(&app.world).ready() {
+				std::thread::sleep(Duration::from_millis(10));
+			}
+			now += FRAME;
+			app.insert_resource(TimeUpdateStrategy::ManualInstant(now));
+			app.update();
+		}
+	}
+}
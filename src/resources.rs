@@ -1,4 +1,8 @@
-use bevy::{prelude::*, render::{render_resource::{TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, Extent3d}, texture::BevyDefault}};
+use bevy::{prelude::*, utils::HashMap, window::PrimaryWindow, render::render_resource::Extent3d};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::post_processing::setup::render_target_image;
 
 #[derive(Resource, Debug, Clone, Default)]
 pub struct ScoreCounter{
@@ -6,7 +10,7 @@ pub struct ScoreCounter{
 	pub timer: Timer,
 }
 
-#[derive(Resource, PartialEq, Debug, Clone, Default)]
+#[derive(Resource, Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
 pub struct CursorCabinPosition {
 	pub world_position: Vec2,
 	pub uv_position: Vec2,
@@ -19,12 +23,268 @@ pub struct ProgressBar {
 	pub bad_progress: f32,
 }
 
+// Per fixed step, scaled by how far the controls are pushed
+#[derive(Resource, PartialEq, Debug, Clone, Copy)]
+pub struct ShipHandling {
+	pub thrust_acceleration: f32,
+	pub turn_acceleration: f32,
+}
+
+#[derive(Resource, PartialEq, Debug, Clone, Copy)]
+pub struct SpeedLimits {
+	// Speed above this bleeds away instead of being cut off
+	pub soft_cap: f32,
+	// Rate per second at which the excess bleeds away
+	pub softness: f32,
+	pub hyperspace_entry: f32,
+	// Lower than the entry speed, so hovering around one speed doesn't flicker in and out
+	pub hyperspace_exit: f32,
+}
+
+#[derive(Resource, PartialEq, Debug, Clone, Copy)]
+pub struct AutopilotParameters {
+	// How long the controls need to be left alone before the autopilot takes over
+	pub idle_seconds: f32,
+	// How far it looks for thoughts to chase
+	pub search_radius: f32,
+	pub cruise_speed: f32,
+	// Collected thoughts it waits for before pressing merge
+	pub merge_at: usize,
+}
+
+#[derive(Resource, PartialEq, Debug, Clone, Copy)]
+pub struct BoostParameters {
+	pub impulse: f32,
+	// Fuel used up by one boost
+	pub cost: f32,
+	// Seconds between boosts
+	pub cooldown: f32,
+	pub merge_recharge: f32,
+	// Per point of intensity of a collected positive thought
+	pub positive_thought_recharge: f32,
+}
+
+// Fuel goes from 0 to 1
+#[derive(Resource, Debug, Clone)]
+pub struct BoostFuel {
+	pub fuel: f32,
+	pub cooldown: Timer,
+}
+
+impl BoostFuel {
+	pub fn new(parameters: &BoostParameters) -> Self {
+		let mut cooldown = Timer::from_seconds(parameters.cooldown, TimerMode::Once);
+		// Ready to go from the start
+		cooldown.tick(cooldown.duration());
+		BoostFuel {fuel: 1.0, cooldown}
+	}
+
+	pub fn add(&mut self, fuel: f32) {
+		self.fuel = (self.fuel + fuel).min(1.0);
+	}
+}
+
 #[derive(Resource, PartialEq, Debug, Clone, Copy)]
 pub struct ThoughtSpawnParameters {
 	pub far_radius: f32,
 	pub close_radius: f32,
 	pub despawn_radius: f32,
 	pub total_to_spawn: u32,
+	pub distribution: SpawnDistribution,
+	// Used instead while the ship is in hyperspace, too fast for anything but what's ahead to matter
+	pub hyperspace_distribution: SpawnDistribution,
+}
+
+#[derive(Resource, PartialEq, Debug, Clone, Copy)]
+pub struct HazardSpawnParameters {
+	pub far_radius: f32,
+	pub close_radius: f32,
+	pub despawn_radius: f32,
+	pub total_to_spawn: u32,
+	// Hazards are capsules, half_length is from the middle to the center of a cap
+	pub radius: f32,
+	pub half_length: f32,
+	// Fastest a hazard can spin, in radians per second
+	pub max_tumble: f32,
+	// Speed the ship gets shoved away with on contact
+	pub push: f32,
+	pub bad_progress: f32,
+	// Collected thoughts lost per hit
+	pub knock_loose: usize,
+}
+
+// Where around the player new thoughts show up, always between close_radius and far_radius
+// Cycled through in game with the CycleSpawnDistribution action
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum SpawnDistribution {
+	// Evenly all around
+	Shell,
+	// In front of the ship, along the way it's flying
+	Cone {half_angle: f32},
+	// Clumps of thoughts that all share an emotion
+	Nebulae {cluster_radius: f32, cluster_size: u32},
+}
+
+impl SpawnDistribution {
+	// Default settings of every distribution, in the order they get cycled through
+	pub const ALL: [SpawnDistribution; 3] = [
+		SpawnDistribution::Shell,
+		SpawnDistribution::Cone {half_angle: std::f32::consts::FRAC_PI_4},
+		SpawnDistribution::Nebulae {cluster_radius: 12.0, cluster_size: 15},
+	];
+
+	pub fn next(&self) -> SpawnDistribution {
+		let index = Self::ALL.iter().position(|d| std::mem::discriminant(d) == std::mem::discriminant(self)).unwrap_or(0);
+		Self::ALL[(index + 1) % Self::ALL.len()]
+	}
+}
+
+// How much a thought's intensity changes what it does, `base * intensity^exponent`
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct IntensityCurve {
+	pub base: f32,
+	pub exponent: f32,
+}
+
+impl IntensityCurve {
+	pub fn at(&self, intensity: u32) -> f32 {
+		self.base * (intensity as f32).powf(self.exponent)
+	}
+}
+
+#[derive(Resource, PartialEq, Debug, Clone, Copy)]
+pub struct IntensityScaling {
+	// Size of the thought's cube, which is also its pickup radius
+	pub size: IntensityCurve,
+	// Score for collecting the thought
+	pub score: IntensityCurve,
+	// How far the collected thought moves its progress bar
+	pub progress: IntensityCurve,
+}
+
+// Decides how likely each thought is to spawn, shifting as the game progresses
+#[derive(Resource, PartialEq, Debug, Clone)]
+pub struct ThoughtSpawnTable {
+	// Stages sorted by progress, weights get interpolated between neighbouring stages
+	pub stages: Vec<SpawnTableStage>,
+	// How much a lopsided ProgressBar favours the emotion that is lagging behind, up to 1 neither emotion ever stops spawning
+	pub bar_bias: f32,
+	// Score at which the progress reaches 1, 0 counts as 1
+	pub full_progress_score: u32,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct SpawnTableStage {
+	// Progress between 0 and 1 at which this stage applies fully
+	pub progress: f32,
+	// Weight of each intensity, starting at intensity 1, intensities past the end never spawn
+	pub intensity_weights: Vec<f32>,
+	pub positive_weight: f32,
+	pub negative_weight: f32,
+}
+
+impl Default for ThoughtSpawnTable {
+	// The stages the game plays with
+	fn default() -> Self {
+		ThoughtSpawnTable {
+			stages: vec![
+				// Early on it's mostly mild thoughts
+				SpawnTableStage {progress: 0.0, intensity_weights: vec![1.0, 0.3, 0.0], positive_weight: 1.0, negative_weight: 1.0},
+				SpawnTableStage {progress: 0.4, intensity_weights: vec![1.0, 0.8, 0.05], positive_weight: 1.0, negative_weight: 1.2},
+				// Ascended minds only show up near the end
+				SpawnTableStage {progress: 0.8, intensity_weights: vec![0.6, 1.0, 0.3], positive_weight: 1.0, negative_weight: 1.0},
+			],
+			bar_bias: 1.0,
+			full_progress_score: 1000000,
+		}
+	}
+}
+
+impl ThoughtSpawnTable {
+	pub fn progress(&self, score: u32) -> f32 {
+		(score as f32 / self.full_progress_score.max(1) as f32).clamp(0.0, 1.0)
+	}
+
+	// Relative spawn weight of a thought at the given progress
+	pub fn weight(&self, thought: &crate::thoughts::Thought, progress: f32, bars: &ProgressBar) -> f32 {
+		use crate::thoughts::Emotion;
+
+		let stage_weight = |stage: &SpawnTableStage| {
+			let intensity = stage.intensity_weights.get(thought.intensity.saturating_sub(1) as usize).copied().unwrap_or(0.0);
+			let emotion = match thought.emotion {
+				Emotion::Positive => stage.positive_weight,
+				Emotion::Negative => stage.negative_weight,
+			};
+			intensity * emotion
+		};
+
+		let next = self.stages.iter().position(|stage| stage.progress > progress);
+		let weight = match next {
+			None => self.stages.last().map_or(0.0, stage_weight),
+			Some(0) => stage_weight(&self.stages[0]),
+			Some(i) => {
+				let (from, to) = (&self.stages[i - 1], &self.stages[i]);
+				let t = (progress - from.progress) / (to.progress - from.progress);
+				stage_weight(from) * (1.0 - t) + stage_weight(to) * t
+			}
+		};
+
+		// The bars aren't capped, so the gap is taken relative to how far they've got, which keeps it strictly between -1 and 1.
+		// The extra full bar stops the first few pickups from swinging it all the way.
+		let gap = (bars.bad_progress - bars.good_progress) / (bars.bad_progress.max(0.0) + bars.good_progress.max(0.0) + 1.0);
+		let lag = match thought.emotion {
+			Emotion::Positive => gap,
+			Emotion::Negative => -gap,
+		};
+		(weight * (1.0 + self.bar_bias * lag)).max(0.0)
+	}
+}
+
+// Tones down flashing for players sensitive to it, picked on the disclaimer slide or with `--safe-mode`
+#[derive(Resource, PartialEq, Debug, Clone, Copy)]
+pub struct SafetySettings {
+	pub safe_mode: bool,
+	// Feedback trails past this much smear everything into strobing colours
+	pub max_feedback_alpha: f32,
+	// Per second and colour channel, from 0 to 1, so a full swing takes a quarter second at 4
+	pub max_colour_change: f32,
+}
+
+impl SafetySettings {
+	pub fn feedback_alpha(&self, alpha: f32) -> f32 {
+		if self.safe_mode {alpha.min(self.max_feedback_alpha)} else {alpha}
+	}
+
+	// How far each colour channel may move this frame, anything from 1 up lets everything through
+	pub fn colour_step(&self, delta_seconds: f32) -> f32 {
+		if self.safe_mode {self.max_colour_change * delta_seconds} else {1.0}
+	}
+}
+
+// How big the main render texture is, either fixed or following the window
+#[derive(Resource, PartialEq, Debug, Clone, Copy)]
+pub struct RenderResolution {
+	// Wins over the window size when set
+	pub fixed: Option<UVec2>,
+	// Applied to the window size, below 1 gives chunkier pixels
+	pub scale: f32,
+}
+
+impl Default for RenderResolution {
+	fn default() -> Self {
+		RenderResolution {fixed: None, scale: 1.0}
+	}
+}
+
+impl RenderResolution {
+	// Without a window (or a minimised one) it falls back to 720p
+	pub fn size(&self, window: Option<UVec2>) -> UVec2 {
+		if let Some(fixed) = self.fixed {
+			return fixed;
+		}
+		let window = window.filter(|size| size.x > 0 && size.y > 0).unwrap_or(UVec2::new(1280, 720));
+		(window.as_vec2() * self.scale).round().as_uvec2().max(UVec2::ONE)
+	}
 }
 
 // Texture all the gameplay cameras render to, gets then chewed up by post-proc
@@ -37,31 +297,165 @@ pub struct MainRenderTexture {
 
 impl FromWorld for MainRenderTexture {
 	fn from_world(world: &mut World) -> Self {
+		let resolution = world.get_resource::<RenderResolution>().copied().unwrap_or_default();
+		let window = world.query_filtered::<&Window, With<PrimaryWindow>>().get_single(world).ok()
+			.map(|window| UVec2::new(window.physical_width(), window.physical_height()));
+		let size = resolution.size(window);
 		// Image the main camera renders to
-		let (width, height) = (1280, 720);
 		Self {
-			width, height,
-			texture: {
-				let size = Extent3d {width, height, ..Default::default()};
-				let mut image = Image {
-					texture_descriptor: TextureDescriptor {
-						label: None,
-						size,
-						dimension: TextureDimension::D2,
-						format: TextureFormat::bevy_default(),
-						mip_level_count: 1,
-						sample_count: 1,
-						usage: TextureUsages::TEXTURE_BINDING
-							| TextureUsages::COPY_DST
-							| TextureUsages::RENDER_ATTACHMENT,
-						view_formats: &[],
-					},
-					..default()
-				};
-				// fill image.data with zeroes
-				image.resize(size);
-				world.resource_mut::<Assets<Image>>().add(image)
-			}
+			width: size.x,
+			height: size.y,
+			texture: world.resource_mut::<Assets<Image>>().add(render_target_image(size)),
+		}
+	}
+}
+
+impl MainRenderTexture {
+	pub fn size(&self) -> UVec2 {
+		UVec2::new(self.width, self.height)
+	}
+
+	// Reallocates in place, so everything holding the handle keeps working
+	pub fn resize(&mut self, images: &mut Assets<Image>, size: UVec2) {
+		if let Some(image) = images.get_mut(&self.texture) {
+			image.resize(Extent3d {width: size.x, height: size.y, ..Default::default()});
+		}
+		self.width = size.x;
+		self.height = size.y;
+	}
+}
+
+// Independent random sequences, so one subsystem rolling more dice doesn't shift the others
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum RngStream {
+	ThoughtSpawning,
+	Choreography,
+	Slang,
+	Jukebox,
+	Steering,
+	Debug,
+	Hazards,
+}
+
+impl RngStream {
+	// Fixed by hand so adding a stream doesn't reseed the ones that already exist, never renumber these
+	fn id(self) -> u64 {
+		match self {
+			RngStream::ThoughtSpawning => 0,
+			RngStream::Choreography => 1,
+			RngStream::Slang => 2,
+			RngStream::Jukebox => 3,
+			RngStream::Debug => 4,
+			RngStream::Steering => 5,
+			RngStream::Hazards => 6,
+		}
+	}
+}
+
+// All gameplay randomness comes from here, runs with the same seed play out the same
+#[derive(Resource, Debug, Clone)]
+pub struct GameRng {
+	seed: u64,
+	streams: HashMap<RngStream, StdRng>,
+}
+
+impl GameRng {
+	pub fn from_seed(seed: u64) -> Self {
+		GameRng {seed, streams: HashMap::default()}
+	}
+
+	pub fn seed(&self) -> u64 {
+		self.seed
+	}
+
+	pub fn stream(&mut self, stream: RngStream) -> &mut StdRng {
+		let seed = self.seed;
+		self.streams.entry(stream).or_insert_with(|| {
+			// Spread the stream ids apart so neighbouring seeds don't produce related streams
+			let salt = (stream.id() + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+			StdRng::seed_from_u64(seed ^ salt)
+		})
+	}
+}
+
+impl Default for GameRng {
+	// Seed comes from `--seed <number>` on the command line, or is rolled at random
+	fn default() -> Self {
+		let mut args = std::env::args().skip_while(|arg| arg != "--seed").skip(1);
+		let seed = match args.next().map(|arg| arg.parse::<u64>()) {
+			Some(Ok(seed)) => seed,
+			Some(Err(error)) => {
+				warn!("Could not parse --seed ({}), rolling a random one", error);
+				rand::random()
+			},
+			None => rand::random(),
+		};
+		info!("Game RNG seed: {}", seed);
+		GameRng::from_seed(seed)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::thoughts::{Thought, ThoughtWord, Emotion};
+
+	fn thought(intensity: u32, emotion: Emotion) -> Thought {
+		Thought {
+			image: String::new(),
+			audio: None,
+			intensity,
+			emotion,
+			word: ThoughtWord::Noun(format!("{:?}{}", emotion, intensity)),
+			behaviour: None,
+		}
+	}
+
+	fn library() -> Vec<Thought> {
+		(1..=3).flat_map(|intensity| [thought(intensity, Emotion::Positive), thought(intensity, Emotion::Negative)]).collect()
+	}
+
+	#[test]
+	fn weights_interpolate_between_stages() {
+		let table = ThoughtSpawnTable::default();
+		let mild = thought(1, Emotion::Negative);
+		// Halfway between the first two stages
+		let weight = table.weight(&mild, 0.2, &ProgressBar::default());
+		assert!((weight - 1.1).abs() < 1e-5);
+	}
+
+	#[test]
+	fn zero_full_progress_score_stays_finite() {
+		let table = ThoughtSpawnTable {full_progress_score: 0, ..default()};
+		assert_eq!(table.progress(0), 0.0);
+		assert_eq!(table.progress(10), 1.0);
+		for thought in library() {
+			assert!(table.weight(&thought, table.progress(0), &ProgressBar::default()).is_finite());
+		}
+	}
+
+	#[test]
+	fn a_far_lead_still_spawns_both_emotions() {
+		let table = ThoughtSpawnTable::default();
+		let bars = ProgressBar {good_progress: 4.7, bad_progress: 1.2};
+		let ahead = table.weight(&thought(1, Emotion::Positive), 0.0, &bars);
+		let behind = table.weight(&thought(1, Emotion::Negative), 0.0, &bars);
+		assert!(ahead > 0.0 && behind > ahead, "ahead {} behind {}", ahead, behind);
+
+		// However lopsided it gets, the leading emotion keeps some weight
+		let bars = ProgressBar {good_progress: 1000.0, bad_progress: 0.0};
+		assert!(table.weight(&thought(1, Emotion::Positive), 0.0, &bars) > 0.0);
+	}
+
+	#[test]
+	fn rng_streams_keep_their_seeds() {
+		use rand::{Rng, SeedableRng, rngs::StdRng};
+
+		let seed = 1234;
+		let mut rng = GameRng::from_seed(seed);
+		for (stream, id) in [(RngStream::ThoughtSpawning, 0u64), (RngStream::Debug, 4), (RngStream::Hazards, 6)] {
+			let mut expected = StdRng::seed_from_u64(seed ^ (id + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+			assert_eq!(rng.stream(stream).gen::<u64>(), expected.gen::<u64>(), "{:?}", stream);
 		}
 	}
 }
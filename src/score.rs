@@ -39,9 +39,10 @@ fn add_event_scores(
 	mut counter: ResMut<ScoreCounter>,
 	mut collections: EventReader<ThoughtCollectedEvent>,
 	mut mergers: EventReader<ThoughtCutsceneEndEvent>,
+	scaling: Res<IntensityScaling>,
 ) {
-	for _ in collections.iter() {
-		counter.score += 10000;
+	for collection in collections.iter() {
+		counter.score += scaling.score.at(collection.thought.intensity).round() as u32;
 	}
 	for _ in mergers.iter() {
 		counter.score += 100000;
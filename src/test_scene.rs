@@ -2,7 +2,7 @@ use bevy::prelude::*;
 
 use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
 
-use crate::{prelude::*, GameState};
+use crate::{prelude::*, physics::Shape, GameState};
 
 pub struct SetupPlugin {}
 
@@ -27,16 +27,27 @@ fn scene_setup(
 	let cube = meshes.add(shape::Cube::default().into());
 
 	commands.spawn((
-		Interactable {radius: 1.0},
+		Interactable {shape: Shape::Obb {half_extents: Vec3::splat(0.5)}},
 		AngularVelocity(Vec3::Y),
 		PbrBundle {
-			mesh: cube,
-			material: material,
+			mesh: cube.clone(),
+			material: material.clone(),
 			transform: Transform::from_xyz(0.0, 2.0, 0.0),
 			..default()
 		}
 	));
 
+	// Never turns, so the box can stay lined up with the world
+	commands.spawn((
+		Interactable {shape: Shape::Aabb {half_extents: Vec3::new(2.0, 0.25, 2.0)}},
+		PbrBundle {
+			mesh: cube,
+			material,
+			transform: Transform::from_xyz(0.0, 0.0, 0.0).with_scale(Vec3::new(4.0, 0.5, 4.0)),
+			..default()
+		}
+	));
+
 	commands.spawn(PointLightBundle {
 		point_light: PointLight {
 			intensity: 10000.0,
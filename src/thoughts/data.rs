@@ -1,60 +1,125 @@
-use bevy::prelude::Resource;
+use std::fmt::Display;
+
+use bevy::{prelude::*, reflect::TypeUuid, asset::{AssetLoader, LoadContext, LoadedAsset}, utils::BoxedFuture};
+use serde::Deserialize;
 
 use super::Thought;
-use super::Emotion::*;
-use super::ThoughtWord::*;
 
-#[derive(Resource, Clone)]
+pub const THOUGHT_LIBRARY_PATH: &str = "thoughts/library.thoughts.ron";
+
+// Doubles as the asset loaded from disk and the resource the gameplay reads from
+#[derive(Resource, Deserialize, TypeUuid, Clone, Default, Debug)]
+#[uuid = "5e3a8f0c-6d1b-4c2e-9a47-0b1f2d3c4e5a"]
 pub struct ThoughtLibrary {
+	#[serde(rename = "thoughts")]
 	data: Vec<Thought>
 }
 
-const NO_SOUND: Option::<String> = None;
-
 impl ThoughtLibrary {
-	pub fn new() -> Self {
-		ThoughtLibrary {data: vec![
-//			Thought::new("thoughts/images/debug/1.png",  NO_SOUND, 1, Positive, Noun("1".into())),
-//			Thought::new("thoughts/images/debug/-1.png", NO_SOUND, 1, Negative, Noun("-1".into())),
-//			Thought::new("thoughts/images/debug/2.png",  NO_SOUND, 2, Positive, Noun("2".into())),
-//			Thought::new("thoughts/images/debug/-2.png", NO_SOUND, 2, Negative, Noun("-2".into())),
-			Thought::new("thoughts/images/openclipart/abstract_343040.png",		NO_SOUND, 1, Positive, Noun("The House of the Bird".into())),
-			Thought::new("thoughts/images/openclipart/death_horse_313177.png",	NO_SOUND, 2, Negative, Noun("Incoming death".into())),
-			Thought::new("thoughts/images/openclipart/gamer_grind_214410.png",	NO_SOUND, 1, Positive, Noun("gamer".into())),
-			Thought::new("thoughts/images/openclipart/orange_juice_174090.png",	NO_SOUND, 1, Positive, Noun("orange juice".into())),
-			Thought::new("thoughts/images/openclipart/grape_juice_343001.png",	NO_SOUND, 1, Positive, Noun("grape juice".into())),
-			Thought::new("thoughts/images/openclipart/red_astronaut_279322.png",NO_SOUND, 1, Positive, Noun("astronaut".into())),
-			Thought::new("thoughts/images/openclipart/angry_man_278871.png",	NO_SOUND, 1, Negative, Verb("angers".into())),
-			Thought::new("thoughts/images/openclipart/forest_fire_327561.png",	NO_SOUND, 2, Negative, Noun("forest fire".into())),
-			Thought::new("thoughts/images/openclipart/lighter_343041.png",		NO_SOUND, 1, Positive, Noun("lighter".into())),
-			Thought::new("thoughts/images/openclipart/pain_319678.png",			NO_SOUND, 2, Negative, Noun("pain".into())),
-			Thought::new("thoughts/images/openclipart/brain_306149.png",		NO_SOUND, 1, Positive, Noun("brain".into())),
-			Thought::new("thoughts/images/openclipart/prismatic_mind_327002.png",NO_SOUND,3, Positive, Noun("ascended mind".into())),
-			Thought::new("thoughts/images/openclipart/screw_191883.png",		NO_SOUND, 1, Negative, Noun("screw".into())),
-			Thought::new("thoughts/images/wikimedia/abstract_deities.jpg",		NO_SOUND, 1, Positive, Noun("deity".into())),
-			Thought::new("thoughts/images/wikimedia/bose_einstein.jpg",			NO_SOUND, 1, Positive, Noun("technology".into())),
-			Thought::new("thoughts/images/wikimedia/cursed_stick_figures.jpg",	NO_SOUND, 2, Negative, Noun("Them".into())),
-			Thought::new("thoughts/images/flickr/aligator.jpg",					NO_SOUND, 2, Negative, Noun("aligator".into())),
-			Thought::new("thoughts/images/flickr/chair.jpg",					NO_SOUND, 1, Negative, Noun("chair".into())),
-			Thought::new("thoughts/images/flickr/clouds.jpg",					NO_SOUND, 1, Positive, Noun("clouds".into())),
-			Thought::new("thoughts/images/flickr/decay.jpg",					NO_SOUND, 2, Negative, Verb("decay".into())),
-			Thought::new("thoughts/images/flickr/despair.jpg",					NO_SOUND, 2, Negative, Verb("despair".into())),
-			Thought::new("thoughts/images/flickr/fog_man.jpg",					NO_SOUND, 1, Negative, Noun("Him".into())),
-			Thought::new("thoughts/images/flickr/lock.jpg",						NO_SOUND, 1, Positive, Noun("lock".into())),
-			Thought::new("thoughts/images/flickr/love.jpg",						NO_SOUND, 2, Positive, Verb("love".into())),
-			Thought::new("thoughts/images/flickr/nature.jpg",					NO_SOUND, 2, Positive, Noun("nature".into())),
-		]}
+	#[cfg(test)]
+	pub fn from_thoughts(data: Vec<Thought>) -> Self {
+		ThoughtLibrary {data}
 	}
 
 	pub fn n_thoughts(&self) -> usize {
 		self.data.len()
 	}
 
+	pub fn is_empty(&self) -> bool {
+		self.data.is_empty()
+	}
+
 	pub fn get_thought_by_index(&self, index: usize) -> Thought {
 		self.data[index].clone()
 	}
+
+	// Checks the things serde can't, so a bad entry gets reported instead of blowing up mid-game
+	fn validate(&self) -> Result<(), ThoughtLibraryError> {
+		if self.data.is_empty() {
+			return Err(ThoughtLibraryError::Empty);
+		}
+		for (index, thought) in self.data.iter().enumerate() {
+			if thought.image.is_empty() {
+				return Err(ThoughtLibraryError::MissingImage {index});
+			}
+			if thought.intensity == 0 {
+				return Err(ThoughtLibraryError::ZeroIntensity {index, image: thought.image.clone()});
+			}
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug)]
+pub enum ThoughtLibraryError {
+	Parse(ron::error::SpannedError),
+	Empty,
+	MissingImage {index: usize},
+	ZeroIntensity {index: usize, image: String},
+}
+
+impl Display for ThoughtLibraryError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ThoughtLibraryError::Parse(error) => write!(f, "malformed thought library: {}", error),
+			ThoughtLibraryError::Empty => write!(f, "thought library contains no thoughts"),
+			ThoughtLibraryError::MissingImage {index} => write!(f, "thought #{} has an empty image path", index),
+			ThoughtLibraryError::ZeroIntensity {index, image} => write!(f, "thought #{} ({}) has intensity 0, it needs to be at least 1", index, image),
+		}
+	}
 }
 
-impl Default for ThoughtLibrary {
-    fn default() -> Self {Self::new()}
+impl std::error::Error for ThoughtLibraryError {}
+
+#[derive(Default)]
+pub struct ThoughtLibraryLoader;
+
+impl AssetLoader for ThoughtLibraryLoader {
+	fn load<'a>(
+		&'a self,
+		bytes: &'a [u8],
+		load_context: &'a mut LoadContext,
+	) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+		Box::pin(async move {
+			let library = ron::de::from_bytes::<ThoughtLibrary>(bytes).map_err(ThoughtLibraryError::Parse)?;
+			library.validate()?;
+			load_context.set_default_asset(LoadedAsset::new(library));
+			Ok(())
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["thoughts.ron"]
+	}
+}
+
+// Keeps the library asset alive (and watched for changes)
+#[derive(Resource, Debug, Clone)]
+pub struct ThoughtLibraryHandle(pub Handle<ThoughtLibrary>);
+
+pub fn load_thought_library(mut commands: Commands, asset_server: Res<AssetServer>) {
+	commands.insert_resource(ThoughtLibraryHandle(asset_server.load(THOUGHT_LIBRARY_PATH)));
+}
+
+// Copies the library into the resource whenever it (re)loads
+pub fn update_thought_library(
+	mut commands: Commands,
+	mut events: EventReader<AssetEvent<ThoughtLibrary>>,
+	libraries: Res<Assets<ThoughtLibrary>>,
+	handle: Option<Res<ThoughtLibraryHandle>>,
+) {
+	let Some(handle) = handle else {
+		return;
+	};
+	for event in events.iter() {
+		match event {
+			AssetEvent::Created {handle: changed} | AssetEvent::Modified {handle: changed} if *changed == handle.0 => {
+				if let Some(library) = libraries.get(changed) {
+					info!("Loaded thought library with {} thoughts", library.n_thoughts());
+					commands.insert_resource(library.clone());
+				}
+			},
+			_ => {}
+		}
+	}
 }
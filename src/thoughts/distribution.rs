@@ -0,0 +1,128 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+// Uniformly distributed unit vector
+pub fn random_direction(rng: &mut impl Rng) -> Vec3 {
+	let z = rng.gen_range(-1.0..=1.0_f32);
+	let phi = rng.gen_range(0.0..2.0 * PI);
+	let r = (1.0 - z * z).max(0.0).sqrt();
+	Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// Radius distributed so that points are uniform by volume between the two radii
+fn shell_radius(rng: &mut impl Rng, close_radius: f32, far_radius: f32) -> f32 {
+	let (close_cubed, far_cubed) = (close_radius.powi(3), far_radius.powi(3));
+	if far_cubed <= close_cubed {
+		return close_radius;
+	}
+	rng.gen_range(close_cubed..=far_cubed).cbrt().clamp(close_radius, far_radius)
+}
+
+// Uniform point in the shell between close_radius and far_radius around the origin
+pub fn sample_shell(rng: &mut impl Rng, close_radius: f32, far_radius: f32) -> Vec3 {
+	shell_radius(rng, close_radius, far_radius) * random_direction(rng)
+}
+
+// Uniform point of the shell that lies within half_angle of the axis
+pub fn sample_cone(rng: &mut impl Rng, close_radius: f32, far_radius: f32, axis: Vec3, half_angle: f32) -> Vec3 {
+	let axis = axis.try_normalize().unwrap_or(Vec3::NEG_Z);
+	let cos_theta = 1.0 - rng.gen::<f32>() * (1.0 - half_angle.clamp(0.0, PI).cos());
+	let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+	let phi = rng.gen_range(0.0..2.0 * PI);
+	let direction = Quat::from_rotation_arc(Vec3::Z, axis) * Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+	shell_radius(rng, close_radius, far_radius) * direction
+}
+
+// Uniform point inside a ball
+pub fn sample_ball(rng: &mut impl Rng, radius: f32) -> Vec3 {
+	rng.gen::<f32>().cbrt() * radius * random_direction(rng)
+}
+
+// Point of a cluster around center, pulled back into the shell wherever the cluster pokes out of it
+pub fn sample_in_cluster(rng: &mut impl Rng, center: Vec3, cluster_radius: f32, close_radius: f32, far_radius: f32) -> Vec3 {
+	let point = center + sample_ball(rng, cluster_radius);
+	let distance = point.length();
+	let clamped = distance.min(far_radius).max(close_radius);
+	if clamped == distance {
+		return point;
+	}
+	point.try_normalize().unwrap_or_else(|| random_direction(rng)) * clamped
+}
+
+// Center for a cluster so that the whole cluster fits in the shell, if the shell is thick enough for that
+pub fn sample_cluster_center(rng: &mut impl Rng, close_radius: f32, far_radius: f32, cluster_radius: f32) -> Vec3 {
+	let (close, far) = (close_radius + cluster_radius, far_radius - cluster_radius);
+	if close <= far {
+		sample_shell(rng, close, far)
+	} else {
+		sample_shell(rng, (close_radius + far_radius) / 2.0, (close_radius + far_radius) / 2.0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rand::{SeedableRng, rngs::StdRng};
+
+	use super::*;
+
+	const SAMPLES: usize = 10_000;
+	const CLOSE: f32 = 50.0;
+	const FAR: f32 = 100.0;
+
+	fn in_shell(point: Vec3, close_radius: f32, far_radius: f32) -> bool {
+		let distance = point.length();
+		distance >= close_radius - 1e-3 && distance <= far_radius + 1e-3
+	}
+
+	#[test]
+	fn shell_stays_between_radii() {
+		let mut rng = StdRng::seed_from_u64(1);
+		for _ in 0..SAMPLES {
+			let point = sample_shell(&mut rng, CLOSE, FAR);
+			assert!(in_shell(point, CLOSE, FAR), "{} outside the shell", point);
+		}
+	}
+
+	#[test]
+	fn cone_stays_between_radii_and_inside_the_cone() {
+		let mut rng = StdRng::seed_from_u64(2);
+		let axis = Vec3::new(1.0, 2.0, -3.0).normalize();
+		for half_angle in [0.1, std::f32::consts::FRAC_PI_4, 2.0] {
+			for _ in 0..SAMPLES {
+				let point = sample_cone(&mut rng, CLOSE, FAR, axis, half_angle);
+				assert!(in_shell(point, CLOSE, FAR), "{} outside the shell", point);
+				assert!(point.angle_between(axis) <= half_angle + 1e-3, "{} outside the cone of {}", point, half_angle);
+			}
+		}
+	}
+
+	#[test]
+	fn nebulae_stay_between_radii() {
+		let mut rng = StdRng::seed_from_u64(3);
+		// Thick enough for whole clusters, then thinner than a cluster is wide
+		for (close_radius, far_radius, cluster_radius) in [(CLOSE, FAR, 12.0), (CLOSE, 55.0, 12.0), (CLOSE, CLOSE, 12.0)] {
+			for _ in 0..SAMPLES / 100 {
+				let center = sample_cluster_center(&mut rng, close_radius, far_radius, cluster_radius);
+				for _ in 0..100 {
+					let point = sample_in_cluster(&mut rng, center, cluster_radius, close_radius, far_radius);
+					assert!(in_shell(point, close_radius, far_radius), "{} outside the shell {}..{}", point, close_radius, far_radius);
+					assert!(point.distance(center) <= 2.0 * cluster_radius, "{} strayed from its cluster at {}", point, center);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn nebulae_left_behind_are_pulled_back_into_the_shell() {
+		let mut rng = StdRng::seed_from_u64(4);
+		// The ship flew off and the cluster is now right on top of it, or far behind
+		for center in [Vec3::ZERO, Vec3::X * 300.0] {
+			for _ in 0..SAMPLES {
+				let point = sample_in_cluster(&mut rng, center, 12.0, CLOSE, FAR);
+				assert!(in_shell(point, CLOSE, FAR), "{} outside the shell", point);
+			}
+		}
+	}
+}
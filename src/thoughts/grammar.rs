@@ -0,0 +1,191 @@
+use super::{Thought, ThoughtWord, Emotion};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum WordKind {
+	Noun,
+	Verb,
+}
+
+impl ThoughtWord {
+	fn kind(&self) -> WordKind {
+		match self {
+			ThoughtWord::Noun(_) => WordKind::Noun,
+			ThoughtWord::Verb(_) => WordKind::Verb,
+		}
+	}
+
+	pub fn text(&self) -> &str {
+		match self {
+			ThoughtWord::Noun(text) => text,
+			ThoughtWord::Verb(text) => text,
+		}
+	}
+
+	fn with_text(&self, text: &str) -> ThoughtWord {
+		match self.kind() {
+			WordKind::Noun => ThoughtWord::Noun(text.to_string()),
+			WordKind::Verb => ThoughtWord::Verb(text.to_string()),
+		}
+	}
+}
+
+// A sentence shape and how to phrase the words once they're slotted into it
+struct Pattern {
+	kinds: &'static [WordKind],
+	phrase: fn(&[&str]) -> String,
+}
+
+use WordKind::{Noun as N, Verb as V};
+
+// In order of preference, the first one that can be filled wins
+const PATTERNS: [Pattern; 5] = [
+	Pattern {kinds: &[N, V, N], phrase: |w| format!("{} {} {}", noun(w[0]), singular_verb(w[1]), noun(w[2]))},
+	Pattern {kinds: &[N, N, V], phrase: |w| format!("{} and {} {}", noun(w[0]), noun(w[1]), plural_verb(w[2]))},
+	Pattern {kinds: &[N, V, V], phrase: |w| format!("{} {} and {}", noun(w[0]), singular_verb(w[1]), singular_verb(w[2]))},
+	Pattern {kinds: &[N, N, N], phrase: |w| format!("{}, {} and {}", noun(w[0]), noun(w[1]), noun(w[2]))},
+	Pattern {kinds: &[V, V, V], phrase: |w| format!("{}, {} and {}", plural_verb(w[0]), plural_verb(w[1]), plural_verb(w[2]))},
+];
+
+// Nouns written in lowercase are common nouns and get an article, capitalised ones stand on their own
+fn noun(word: &str) -> String {
+	if word.starts_with(char::is_lowercase) {
+		format!("the {}", word)
+	} else {
+		word.to_lowercase()
+	}
+}
+
+fn singular_verb(word: &str) -> String {
+	if word.ends_with('s') {
+		word.to_string()
+	} else {
+		format!("{}s", word)
+	}
+}
+
+fn plural_verb(word: &str) -> String {
+	word.strip_suffix('s').unwrap_or(word).to_string()
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Merge {
+	// Indices into the candidate slice, in sentence order
+	pub order: Vec<usize>,
+	pub sentence: String,
+	// The composite thought the merge produces
+	pub thought: Thought,
+}
+
+// Picks `n` of the candidates that can be arranged into a sentence (only sentences of three words are known for now)
+pub fn merge_thoughts(candidates: &[Thought], n: usize) -> Option<Merge> {
+	for pattern in PATTERNS.iter().filter(|pattern| pattern.kinds.len() == n) {
+		let mut order = Vec::with_capacity(n);
+		for kind in pattern.kinds {
+			let next = (0..candidates.len())
+				.find(|i| candidates[*i].word.kind() == *kind && !order.contains(i));
+			match next {
+				Some(i) => order.push(i),
+				None => break,
+			}
+		}
+		if order.len() != n {
+			continue;
+		}
+
+		let words: Vec<&str> = order.iter().map(|&i| candidates[i].word.text()).collect();
+		let sentence = (pattern.phrase)(&words);
+		let parts: Vec<&Thought> = order.iter().map(|&i| &candidates[i]).collect();
+		let thought = composite_thought(&parts, &sentence);
+		return Some(Merge {order, sentence, thought});
+	}
+	None
+}
+
+fn composite_thought(parts: &[&Thought], sentence: &str) -> Thought {
+	let intensity = parts.iter().map(|thought| thought.intensity).sum();
+	// The stronger feelings win, a tie goes to whatever the sentence starts with
+	let balance: i64 = parts.iter().map(|thought| match thought.emotion {
+		Emotion::Positive => thought.intensity as i64,
+		Emotion::Negative => -(thought.intensity as i64),
+	}).sum();
+	let emotion = match balance {
+		b if b > 0 => Emotion::Positive,
+		b if b < 0 => Emotion::Negative,
+		_ => parts[0].emotion,
+	};
+	Thought {
+		image: parts[0].image.clone(),
+		audio: parts.iter().find_map(|thought| thought.audio.clone()),
+		intensity,
+		emotion,
+		// Composites aren't merged again, the sentence just keeps the kind of its first word
+		word: parts[0].word.with_text(sentence),
+		behaviour: None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn thought(word: ThoughtWord, emotion: Emotion, intensity: u32) -> Thought {
+		Thought {image: format!("{}.png", word.text()), audio: None, intensity, emotion, word, behaviour: None}
+	}
+
+	fn noun(text: &str) -> Thought {
+		thought(ThoughtWord::Noun(text.into()), Emotion::Positive, 1)
+	}
+
+	fn verb(text: &str) -> Thought {
+		thought(ThoughtWord::Verb(text.into()), Emotion::Positive, 1)
+	}
+
+	#[test]
+	fn the_first_pattern_that_fits_wins() {
+		let merge = merge_thoughts(&[verb("hum"), noun("sun"), noun("Moon"), verb("sings")], 3).unwrap();
+		assert_eq!(merge.order, vec![1, 0, 2]);
+		assert_eq!(merge.sentence, "the sun hums moon");
+
+		let merge = merge_thoughts(&[verb("hum"), noun("Home"), verb("glow")], 3).unwrap();
+		assert_eq!(merge.sentence, "home hums and glows");
+	}
+
+	#[test]
+	fn lists_are_the_last_resort() {
+		let nouns = merge_thoughts(&[noun("sun"), noun("Home"), noun("sea")], 3).unwrap();
+		assert_eq!(nouns.sentence, "the sun, home and the sea");
+
+		let verbs = merge_thoughts(&[verb("hums"), verb("glow"), verb("sings")], 3).unwrap();
+		assert_eq!(verbs.sentence, "hum, glow and sing");
+	}
+
+	#[test]
+	fn nothing_fits_without_enough_words() {
+		assert!(merge_thoughts(&[noun("sun"), verb("hums")], 3).is_none());
+		// Only sentences of three words are known
+		assert!(merge_thoughts(&[noun("sun"), verb("hums"), noun("sea"), noun("sky")], 4).is_none());
+	}
+
+	#[test]
+	fn the_composite_takes_the_stronger_feeling() {
+		let candidates = [
+			thought(ThoughtWord::Noun("sun".into()), Emotion::Positive, 2),
+			thought(ThoughtWord::Verb("burns".into()), Emotion::Negative, 3),
+			thought(ThoughtWord::Noun("sea".into()), Emotion::Positive, 2),
+		];
+		let merge = merge_thoughts(&candidates, 3).unwrap();
+		assert_eq!(merge.thought.intensity, 7);
+		assert_eq!(merge.thought.emotion, Emotion::Positive);
+		assert_eq!(merge.thought.image, "sun.png");
+		assert_eq!(merge.thought.word, ThoughtWord::Noun("the sun burns the sea".into()));
+
+		let tied = [
+			thought(ThoughtWord::Verb("hums".into()), Emotion::Negative, 2),
+			thought(ThoughtWord::Verb("glows".into()), Emotion::Positive, 1),
+			thought(ThoughtWord::Verb("sings".into()), Emotion::Positive, 1),
+		];
+		let merge = merge_thoughts(&tied, 3).unwrap();
+		assert_eq!(merge.thought.emotion, Emotion::Negative);
+		assert_eq!(merge.thought.word, ThoughtWord::Verb("hum, glow and sing".into()));
+	}
+}
@@ -1,66 +1,90 @@
 use bevy::prelude::*;
+use serde::Deserialize;
 
 pub mod data;
+pub mod distribution;
+pub mod grammar;
+pub mod pool;
+pub mod steering;
 mod systems;
 
-use crate::{prelude::*, physics::PhysicsSystemSet, GameState};
+use crate::{prelude::*, physics::{PhysicsSystemSet, InteractionSystemSet}, GameState};
 
-use self::{systems::*, data::ThoughtLibrary};
+use self::{systems::*, data::*, pool::*, steering::*};
 
 pub struct ThoughtsPlugin;
 
 impl Plugin for ThoughtsPlugin {
 	fn build(&self, app: &mut App) {
 		app
+		.add_asset::<ThoughtLibrary>()
+		.init_asset_loader::<ThoughtLibraryLoader>()
 		.init_resource::<ThoughtLibrary>()
+		.init_resource::<ThoughtAssets>()
+		.init_resource::<ThoughtPool>()
+		.add_startup_system(load_thought_library)
+		.add_system(update_thought_library)
+		.add_system(release_recycled_thoughts.in_base_set(CoreSet::First))
 		.insert_resource(ThoughtSpawnParameters {
 			far_radius: 100.0,
 			close_radius: 50.0,
 			total_to_spawn: 500,
 		    despawn_radius: 150.0,
+			distribution: SpawnDistribution::Shell,
+			hyperspace_distribution: SpawnDistribution::Cone {half_angle: 0.5},
+		})
+		.init_resource::<ThoughtSpawnTable>()
+		.insert_resource(IntensityScaling {
+			size: IntensityCurve {base: THOUGHT_SIZE, exponent: 0.5},
+			score: IntensityCurve {base: 10000.0, exponent: 1.5},
+			progress: IntensityCurve {base: 0.1, exponent: 1.0},
+		})
+		.insert_resource(SteeringParameters {
+			max_speed: 8.0,
+			max_force: 4.0,
+			awareness_radius: 40.0,
+			orbit_radius: 15.0,
+			swarm_radius: 10.0,
+			wander_jitter: 2.0,
 		})
 		.add_event::<ThoughtCollectedEvent>()
+		.add_system(cycle_spawn_distribution.before(spawn_thoughts).run_if(in_state(GameState::Game)))
+		.add_system(steer_thoughts.before(PhysicsSystemSet).in_schedule(CoreSchedule::FixedUpdate).run_if(in_state(GameState::Game)))
 		.add_systems((
 			spawn_thoughts,
 			rotate_thoughts,
 			collect_thoughts,
 			despawn_thoughts,
-		).after(PhysicsSystemSet).distributive_run_if(in_state(GameState::Game)));
+		).after(InteractionSystemSet).distributive_run_if(in_state(GameState::Game)));
 	}
 }
 
-#[derive(Component, PartialEq, Eq, Clone, Debug)]
+#[derive(Component, Deserialize, PartialEq, Eq, Clone, Debug)]
 pub struct Thought {
 	pub image: String,
+	#[serde(default)]
 	pub audio: Option<String>,
 	pub intensity: u32,
 	pub emotion: Emotion,
-	pub word: ThoughtWord
+	pub word: ThoughtWord,
+	// Thoughts without a behaviour stay where they spawned
+	#[serde(default)]
+	pub behaviour: Option<Behaviour>,
 }
 
-#[derive(PartialEq, Eq, Clone, Debug)]
+#[derive(Deserialize, PartialEq, Eq, Clone, Debug)]
 pub enum ThoughtWord {
 	Noun(String),
 	Verb(String),
 }
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
 pub enum Emotion {
 	Positive,
 	Negative
 }
 
 impl Thought {
-	pub fn new(image: impl Into<String>, audio: Option<impl Into<String>>, intensity: u32, emotion: Emotion, word: ThoughtWord) -> Self {
-		Thought {
-			image: image.into(),
-			audio: audio.map(Into::into),
-			intensity,
-			emotion,
-			word
-		}
-	}
-
 	pub fn load_image(&self, assets: &AssetServer) -> Handle<Image> {
 		assets.load(self.image.clone())
 	}
@@ -77,7 +101,7 @@ impl Thought {
 		}
 	}
 
-	/*pub fn load_sound(&self, assets: &AssetServer) -> Handle<> {
-		audio: self.audio.clone().map(|audio_file| {assets.load(audio_file)}),
-	}*/
+	pub fn load_sound(&self, assets: &AssetServer) -> Option<Handle<bevy_kira_audio::AudioSource>> {
+		self.audio.clone().map(|audio_file| assets.load(audio_file))
+	}
 }
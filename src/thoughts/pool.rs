@@ -0,0 +1,99 @@
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+
+use crate::{prelude::*, physics::PhysicsState};
+
+use super::steering::Steering;
+
+// Size of the shared cube mesh, thoughts get scaled from it
+pub const THOUGHT_SIZE: f32 = 2.5;
+
+// Assets shared by every thought in the world, so spawning doesn't keep adding new ones
+#[derive(Resource, Debug, Clone)]
+pub struct ThoughtAssets {
+	pub mesh: Handle<Mesh>,
+	// One material per image
+	materials: HashMap<String, Handle<StandardMaterial>>,
+}
+
+impl FromWorld for ThoughtAssets {
+	fn from_world(world: &mut World) -> Self {
+		ThoughtAssets {
+			mesh: world.resource_mut::<Assets<Mesh>>().add(Mesh::from(shape::Cube::new(THOUGHT_SIZE))),
+			materials: HashMap::default(),
+		}
+	}
+}
+
+impl ThoughtAssets {
+	pub fn material(
+		&mut self,
+		thought: &Thought,
+		materials: &mut Assets<StandardMaterial>,
+		asset_server: &AssetServer,
+	) -> Handle<StandardMaterial> {
+		self.materials.entry(thought.image.clone())
+			.or_insert_with(|| materials.add(thought.create_material(asset_server)))
+			.clone()
+	}
+}
+
+// Hidden thought entities waiting to be reused instead of spawning new ones
+#[derive(Resource, Debug, Clone, Default)]
+pub struct ThoughtPool {
+	free: Vec<Entity>,
+	// Let go of this frame, their removals are still sitting in the command queue so they can't be handed out yet
+	recycled: Vec<Entity>,
+	// Guards against the same thought being let go twice in one frame
+	pooled: HashSet<Entity>,
+}
+
+impl ThoughtPool {
+	// Strips the entity of being a thought and puts it aside
+	pub fn recycle(&mut self, commands: &mut Commands, entity: Entity) {
+		if self.pooled.insert(entity) {
+			commands.entity(entity)
+				.remove::<(Thought, Interactable, Steering, Velocity, VelocityDrag, PhysicsState)>()
+				.insert(Visibility::Hidden);
+			self.recycled.push(entity);
+		}
+	}
+
+	// Makes last frame's recycled thoughts available, by now their commands have been applied
+	pub fn release_recycled(&mut self) {
+		self.free.append(&mut self.recycled);
+	}
+
+	pub fn take(&mut self) -> Option<Entity> {
+		let entity = self.free.pop()?;
+		self.pooled.remove(&entity);
+		Some(entity)
+	}
+}
+
+pub fn release_recycled_thoughts(mut pool: ResMut<ThoughtPool>) {
+	pool.release_recycled();
+}
+
+#[cfg(test)]
+mod tests {
+	use bevy::ecs::system::CommandQueue;
+
+	use super::*;
+
+	#[test]
+	fn recycled_thoughts_wait_a_frame() {
+		let mut world = World::new();
+		let entity = world.spawn_empty().id();
+		let mut queue = CommandQueue::default();
+		let mut pool = ThoughtPool::default();
+
+		pool.recycle(&mut Commands::new(&mut queue, &world), entity);
+		pool.recycle(&mut Commands::new(&mut queue, &world), entity);
+		assert_eq!(pool.take(), None);
+
+		queue.apply(&mut world);
+		pool.release_recycled();
+		assert_eq!(pool.take(), Some(entity));
+		assert_eq!(pool.take(), None);
+	}
+}
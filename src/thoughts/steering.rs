@@ -0,0 +1,116 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+
+use crate::{prelude::*, physics::SpatialGrid};
+
+use super::distribution::random_direction;
+
+// How a thought moves on its own, picked per thought in the library
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Behaviour {
+	// Drifts around aimlessly
+	Wander,
+	// Circles the ship once it comes close
+	Orbit,
+	// Keeps away from the ship
+	Flee,
+	// Goes after the ship
+	Chase,
+	// Flocks together with other swarming thoughts
+	Swarm,
+}
+
+#[derive(Component, PartialEq, Debug, Clone, Copy)]
+pub struct Steering {
+	pub behaviour: Behaviour,
+	// Current heading while wandering
+	wander: Vec3,
+}
+
+impl Steering {
+	pub fn new(behaviour: Behaviour) -> Self {
+		Steering {behaviour, wander: Vec3::ZERO}
+	}
+}
+
+#[derive(Resource, PartialEq, Debug, Clone, Copy)]
+pub struct SteeringParameters {
+	pub max_speed: f32,
+	// Largest change of velocity per second
+	pub max_force: f32,
+	// Thoughts react to the ship only when it's closer than this
+	pub awareness_radius: f32,
+	pub orbit_radius: f32,
+	// How far swarming thoughts see their neighbours
+	pub swarm_radius: f32,
+	// How quickly the wandering heading turns
+	pub wander_jitter: f32,
+}
+
+pub fn steer_thoughts(
+	mut thoughts: Query<(Entity, &Transform, &mut Velocity, &mut Steering)>,
+	player: Query<&Transform, (With<Player>, Without<Steering>)>,
+	grid: Res<SpatialGrid>,
+	parameters: Res<SteeringParameters>,
+	mut rng: ResMut<GameRng>,
+	fixed_time: Res<FixedTime>,
+) {
+	let Ok(player) = player.get_single() else {
+		return;
+	};
+	let dt = fixed_time.period.as_secs_f32();
+	let rng = rng.stream(RngStream::Steering);
+
+	// Swarms need to see each others velocities from before this frame's changes
+	let swarm: HashMap<Entity, (Vec3, Vec3)> = thoughts.iter()
+		.filter(|(_, _, _, steering)| steering.behaviour == Behaviour::Swarm)
+		.map(|(entity, transform, velocity, _)| (entity, (transform.translation, velocity.0)))
+		.collect();
+
+	for (entity, transform, mut velocity, mut steering) in thoughts.iter_mut() {
+		let position = transform.translation;
+		let offset = position - player.translation;
+		let aware = offset.length_squared() < parameters.awareness_radius * parameters.awareness_radius;
+		let away = offset.normalize_or_zero();
+
+		let wander = |steering: &mut Steering, rng: &mut rand::rngs::StdRng| {
+			steering.wander = (steering.wander + parameters.wander_jitter * dt * random_direction(rng)).normalize_or_zero();
+			0.5 * parameters.max_speed * steering.wander
+		};
+
+		let desired = match steering.behaviour {
+			Behaviour::Wander => wander(&mut steering, rng),
+			Behaviour::Chase if aware => -parameters.max_speed * away,
+			Behaviour::Flee if aware => parameters.max_speed * away,
+			Behaviour::Orbit if aware => {
+				let tangent = player.up().cross(away).normalize_or_zero();
+				let correction = (parameters.orbit_radius - offset.length()) / parameters.orbit_radius * away;
+				parameters.max_speed * (tangent + correction).normalize_or_zero()
+			},
+			Behaviour::Swarm => {
+				let (mut count, mut center, mut heading, mut separation) = (0, Vec3::ZERO, Vec3::ZERO, Vec3::ZERO);
+				for neighbour in grid.query_radius(position, parameters.swarm_radius) {
+					if let Some((neighbour_position, neighbour_velocity)) = swarm.get(&neighbour.entity).filter(|_| neighbour.entity != entity) {
+						let apart = position - *neighbour_position;
+						count += 1;
+						center += *neighbour_position;
+						heading += *neighbour_velocity;
+						separation += apart / apart.length_squared().max(0.01);
+					}
+				}
+				if count == 0 {
+					wander(&mut steering, rng)
+				} else {
+					let cohesion = (center / count as f32 - position).normalize_or_zero();
+					let alignment = heading.normalize_or_zero();
+					0.5 * parameters.max_speed * (cohesion + alignment + parameters.swarm_radius * separation).normalize_or_zero()
+				}
+			},
+			// Not close enough to care about the ship
+			_ => Vec3::ZERO,
+		};
+
+		let steer = (desired - velocity.0).clamp_length_max(parameters.max_force);
+		velocity.0 += steer * dt;
+	}
+}
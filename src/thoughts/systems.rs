@@ -1,78 +1,178 @@
-use bevy::prelude::*;
-use crate::prelude::*;
-use rand::Rng;
+use bevy::{prelude::*, ecs::system::SystemParam};
+use crate::{prelude::*, physics::{SpatialGrid, Shape}, player::ShipState, input::{ActionState, Action}};
+use rand::{Rng, distributions::WeightedIndex, prelude::Distribution};
 
-use super::data::ThoughtLibrary;
+use super::{data::ThoughtLibrary, Emotion, distribution::*, pool::*, steering::Steering};
+
+// The cluster currently being filled when spawning nebulae
+pub struct Nebula {
+	center: Vec3,
+	emotion: Emotion,
+	remaining: u32,
+}
+
+// What decides which thoughts spawn and where
+#[derive(SystemParam)]
+pub struct SpawnRules<'w> {
+	thoughts: Res<'w, ThoughtLibrary>,
+	spawn: Res<'w, ThoughtSpawnParameters>,
+	scaling: Res<'w, IntensityScaling>,
+	table: Res<'w, ThoughtSpawnTable>,
+	score: Option<Res<'w, ScoreCounter>>,
+	bars: Res<'w, ProgressBar>,
+	ship_state: Res<'w, State<ShipState>>,
+}
+
+// What it takes to put a thought into the world
+#[derive(SystemParam)]
+pub struct ThoughtSpawner<'w, 's> {
+	commands: Commands<'w, 's>,
+	thought_assets: ResMut<'w, ThoughtAssets>,
+	pool: ResMut<'w, ThoughtPool>,
+	materials: ResMut<'w, Assets<StandardMaterial>>,
+	asset_server: Res<'w, AssetServer>,
+}
 
 pub fn spawn_thoughts(
-	mut commands: Commands,
-	mut mesh: ResMut<Assets<Mesh>>,
-	mut materials: ResMut<Assets<StandardMaterial>>,
+	mut spawner: ThoughtSpawner,
+	rules: SpawnRules,
 	thoughts_entites: Query<Entity, With<Thought>>,
-	player_transform: Query<&Transform, With<Player>>,
-	asset_server: Res<AssetServer>,
-	thoughts: Res<ThoughtLibrary>,
-	spawn: Res<ThoughtSpawnParameters>,
+	player: Query<(&Transform, Option<&Velocity>), With<Player>>,
+	mut rng: ResMut<GameRng>,
+	mut nebula: Local<Option<Nebula>>,
 ) {
+	let SpawnRules {thoughts, spawn, scaling, table, score, bars, ship_state} = rules;
+	// Library hasn't loaded yet
+	if thoughts.is_empty() {
+		return;
+	}
+
 	let mut already_spawned = thoughts_entites.iter().count() as u32;
+	if already_spawned >= spawn.total_to_spawn {
+		return;
+	}
+
+	let progress = table.progress(score.map_or(0, |score| score.score));
+	let weights: Vec<f32> = (0..thoughts.n_thoughts()).map(|i| table.weight(&thoughts.get_thought_by_index(i), progress, &bars)).collect();
+	let distribution = match WeightedIndex::new(&weights) {
+		Ok(distribution) => distribution,
+		Err(error) => {
+			warn!("Thought spawn table gives no usable weights ({}), spawning uniformly", error);
+			WeightedIndex::new(vec![1.0; thoughts.n_thoughts()]).unwrap()
+		}
+	};
+	// Same weights, but only for the thoughts of one emotion
+	let emotion_distribution = |emotion: Emotion| WeightedIndex::new((0..thoughts.n_thoughts()).map(|i| {
+		if thoughts.get_thought_by_index(i).emotion == emotion {weights[i]} else {0.0}
+	})).ok();
 
-	let player_position = if let Ok(player) = player_transform.get_single() {
-		player.translation
+	let (player_position, heading) = if let Ok((player, velocity)) = player.get_single() {
+		let heading = velocity.and_then(|v| v.0.try_normalize()).unwrap_or(player.forward());
+		(player.translation, heading)
 	} else {
-		Vec3::ZERO
+		(Vec3::ZERO, Vec3::NEG_Z)
+	};
+
+	let spawn_distribution = match ship_state.0 {
+		ShipState::Cruising => spawn.distribution,
+		ShipState::Hyperspace => spawn.hyperspace_distribution,
 	};
 
 	let mut spawning_capacity = 10;
-	let mut rng = rand::thread_rng();
+	let rng = rng.stream(RngStream::ThoughtSpawning);
 	while spawn.total_to_spawn > already_spawned && spawning_capacity > 0 {
 		spawning_capacity -= 1;
-		let thought_id: usize = rng.gen();
-		let thought = thoughts.get_thought_by_index(thought_id % thoughts.n_thoughts());
-		let location = player_position +
-		if spawn.far_radius * spawn.far_radius > 2.0 * spawn.close_radius * spawn.close_radius {
-			loop {
-				let x = (2.0 * rng.gen::<f32>() - 1.0) * spawn.far_radius;
-				let y = (2.0 * rng.gen::<f32>() - 1.0) * spawn.far_radius;
-				let z = (2.0 * rng.gen::<f32>() - 1.0) * spawn.far_radius;
-				let r2 = x*x+y*y+z*z;
-				if r2 <= spawn.far_radius * spawn.far_radius && r2 >= spawn.close_radius * spawn.close_radius {
-					break Vec3::new(x,y,z);
-				}
-			}
-		} else {
-			warn!("Spawning far radius and close radius are very close to each other, RNG might converge slowly");
-			let x = (2.0 * rng.gen::<f32>() - 1.0) * spawn.far_radius;
-			let y = (2.0 * rng.gen::<f32>() - 1.0) * spawn.far_radius;
-			let z = (2.0 * rng.gen::<f32>() - 1.0) * spawn.far_radius;
-			Vec3::new(x,y,z)
+		let (thought_index, location) = match spawn_distribution {
+			SpawnDistribution::Shell => (
+				distribution.sample(rng),
+				player_position + sample_shell(rng, spawn.close_radius, spawn.far_radius),
+			),
+			SpawnDistribution::Cone {half_angle} => (
+				distribution.sample(rng),
+				player_position + sample_cone(rng, spawn.close_radius, spawn.far_radius, heading, half_angle),
+			),
+			SpawnDistribution::Nebulae {cluster_radius, cluster_size} => {
+				let current = match nebula.as_mut() {
+					Some(current) if current.remaining > 0 => current,
+					_ => nebula.insert(Nebula {
+						center: player_position + sample_cluster_center(rng, spawn.close_radius, spawn.far_radius, cluster_radius),
+						emotion: if rng.gen_bool(0.5) {Emotion::Positive} else {Emotion::Negative},
+						remaining: cluster_size,
+					}),
+				};
+				current.remaining -= 1;
+				let index = match emotion_distribution(current.emotion) {
+					Some(emotion_distribution) => emotion_distribution.sample(rng),
+					None => distribution.sample(rng),
+				};
+				(index, player_position + sample_in_cluster(rng, current.center - player_position, cluster_radius, spawn.close_radius, spawn.far_radius))
+			},
 		};
-		spawn_thought(&mut commands, &mut mesh, &mut materials, &asset_server, thought, location);
+		let thought = thoughts.get_thought_by_index(thought_index);
+		let size = scaling.size.at(thought.intensity);
+		spawner.spawn(thought, location, size);
 		already_spawned += 1;
 	}
 }
 
+impl ThoughtSpawner<'_, '_> {
+	// Reuses a pooled entity if there is one
+	fn spawn(&mut self, thought: Thought, location: Vec3, size: f32) -> Entity {
+		let material = self.thought_assets.material(&thought, &mut self.materials, &self.asset_server);
+		spawn_thought(&mut self.commands, &mut self.pool, &self.thought_assets, material, thought, location, size)
+	}
+}
+
 fn spawn_thought(
 	commands: &mut Commands,
-	mesh: &mut ResMut<Assets<Mesh>>,
-	materials: &mut ResMut<Assets<StandardMaterial>>,
-	asset_server: &Res<AssetServer>,
+	pool: &mut ThoughtPool,
+	thought_assets: &ThoughtAssets,
+	material: Handle<StandardMaterial>,
 	thought: Thought,
 	location: Vec3,
+	size: f32,
 ) -> Entity {
-	let thought_size = 2.5;
-
-	commands.spawn((
-		Name::new(format!("Thought {:?}@[{:.1};{:.1};{:.1}]", thought.word, location.x, location.y, location.z)),
-		MaterialMeshBundle {
-			mesh: mesh.add(Mesh::from(shape::Cube::new(thought_size))),
-			material: materials.add(thought.create_material(&asset_server)),
-			transform: Transform::from_translation(location),
-			..default()
-		},
-		thought,
-		Interactable {radius: thought_size},
-	))
-	.id()
+	let name = Name::new(format!("Thought {:?}@[{:.1};{:.1};{:.1}]", thought.word, location.x, location.y, location.z));
+	let transform = Transform::from_translation(location).with_scale(Vec3::splat(size / THOUGHT_SIZE));
+	let interactable = Interactable {shape: Shape::Sphere {radius: size}};
+	let behaviour = thought.behaviour;
+
+	let mut entity = if let Some(entity) = pool.take() {
+		let mut entity = commands.entity(entity);
+		entity.insert((
+			name,
+			material,
+			transform,
+			Visibility::Inherited,
+			thought,
+			interactable,
+		));
+		entity
+	} else {
+		commands.spawn((
+			name,
+			MaterialMeshBundle {
+				mesh: thought_assets.mesh.clone(),
+				material,
+				transform,
+				..default()
+			},
+			thought,
+			interactable,
+		))
+	};
+
+	if let Some(behaviour) = behaviour {
+		entity.insert((Steering::new(behaviour), Velocity::default(), VelocityDrag(0.5)));
+	}
+	entity.id()
+}
+
+pub fn cycle_spawn_distribution(actions: Res<ActionState>, mut spawn: ResMut<ThoughtSpawnParameters>) {
+	if actions.just_pressed(Action::CycleSpawnDistribution) {
+		spawn.distribution = spawn.distribution.next();
+		info!("Spawn distribution: {:?}", spawn.distribution);
+	}
 }
 
 pub fn rotate_thoughts(
@@ -87,16 +187,17 @@ pub fn rotate_thoughts(
 
 pub fn collect_thoughts(
 	mut commands: Commands,
+	mut pool: ResMut<ThoughtPool>,
 	players: Query<Entity, With<Player>>,
-	thoughts: Query<(Entity, &Thought), Without<Player>>,
+	thoughts: Query<(Entity, &Thought, &Transform), Without<Player>>,
 	mut collisions: EventReader<PlayerInteractionEvent>,
 	mut banana: EventWriter<ThoughtCollectedEvent>
 ) {
-	for event in collisions.iter() {
+	for event in collisions.iter().filter(|event| event.phase == InteractionPhase::Started) {
 		if let (Ok(player), Ok(thought)) = (players.get(event.player), thoughts.get(event.other)) {
-			commands.entity(thought.0).despawn_recursive();
+			pool.recycle(&mut commands, thought.0);
 			banana.send(ThoughtCollectedEvent {
-				player, thought: thought.1.clone()
+				player, thought: thought.1.clone(), position: thought.2.translation
 			});
 		}
 	}
@@ -104,15 +205,188 @@ pub fn collect_thoughts(
 
 pub fn despawn_thoughts(
 	mut commands: Commands,
-	thoughts_entites: Query<(Entity, &Transform), With<Thought>>,
+	mut pool: ResMut<ThoughtPool>,
+	thoughts_entites: Query<(), With<Thought>>,
 	player_transform: Query<&Transform, With<Player>>,
+	grid: Res<SpatialGrid>,
 	despawn: Res<ThoughtSpawnParameters>,
 ) {
 	if let Ok(player_transform) = player_transform.get_single() {
-		for (entity, transform) in thoughts_entites.iter() {
-			if (transform.translation - player_transform.translation).length() > despawn.despawn_radius {
-				commands.entity(entity).despawn_recursive();
+		for entry in grid.outside_radius(player_transform.translation, despawn.despawn_radius) {
+			if thoughts_entites.contains(entry.entity) {
+				pool.recycle(&mut commands, entry.entity);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bevy::asset::AssetPlugin;
+
+	use super::*;
+	use crate::thoughts::ThoughtWord;
+
+	const PER_FRAME: u32 = 10;
+
+	fn thought(image: &str, intensity: u32) -> Thought {
+		Thought {
+			image: image.to_string(),
+			audio: None,
+			intensity,
+			emotion: Emotion::Positive,
+			word: ThoughtWord::Noun(image.to_string()),
+			behaviour: None,
+		}
+	}
+
+	// Lets go of every thought, the way collecting or flying away from them does
+	fn recycle_all(mut commands: Commands, mut pool: ResMut<ThoughtPool>, thoughts: Query<Entity, With<Thought>>) {
+		for entity in thoughts.iter() {
+			pool.recycle(&mut commands, entity);
+		}
+	}
+
+	// Everything spawn_thoughts needs, but no systems yet
+	fn spawn_app(library: Vec<Thought>, table: ThoughtSpawnTable, total_to_spawn: u32) -> App {
+		let mut app = App::new();
+		app
+		.add_plugins(MinimalPlugins)
+		.add_plugin(AssetPlugin::default())
+		.add_asset::<Image>()
+		.add_asset::<Mesh>()
+		.add_asset::<StandardMaterial>()
+		.add_state::<ShipState>()
+		.init_resource::<ThoughtAssets>()
+		.init_resource::<ThoughtPool>()
+		.init_resource::<ProgressBar>()
+		.insert_resource(GameRng::from_seed(8))
+		.insert_resource(ThoughtLibrary::from_thoughts(library))
+		.insert_resource(ThoughtSpawnParameters {
+			far_radius: 100.0,
+			close_radius: 50.0,
+			total_to_spawn,
+			despawn_radius: 150.0,
+			distribution: SpawnDistribution::Shell,
+			hyperspace_distribution: SpawnDistribution::Shell,
+		})
+		.insert_resource(IntensityScaling {
+			size: IntensityCurve {base: THOUGHT_SIZE, exponent: 0.5},
+			score: IntensityCurve {base: 1.0, exponent: 1.0},
+			progress: IntensityCurve {base: 0.1, exponent: 1.0},
+		})
+		.insert_resource(table);
+		app.world.spawn((Player, Transform::default()));
+		app
+	}
+
+	fn churn_app() -> App {
+		let table = ThoughtSpawnTable {
+			stages: vec![SpawnTableStage {progress: 0.0, intensity_weights: vec![1.0, 1.0, 1.0], positive_weight: 1.0, negative_weight: 1.0}],
+			bar_bias: 0.0,
+			full_progress_score: 1,
+		};
+		let mut app = spawn_app(vec![thought("a.png", 1), thought("b.png", 2), thought("c.png", 3)], table, PER_FRAME);
+		app
+		.add_system(release_recycled_thoughts.in_base_set(CoreSet::First))
+		// Recycling first means spawning would grab entities whose removals haven't been applied yet
+		.add_systems((recycle_all, spawn_thoughts).chain());
+		app
+	}
+
+	// Every intensity in both emotions, in the order weights and counts come back in
+	fn library() -> Vec<Thought> {
+		(1..=3).flat_map(|intensity| [
+			thought(&format!("good{}.png", intensity), intensity),
+			Thought {emotion: Emotion::Negative, ..thought(&format!("bad{}.png", intensity), intensity)},
+		]).collect()
+	}
+
+	// Lets spawn_thoughts fill the world with the game's spawn table and returns how often each thought came up
+	fn spawned_counts(score: u32, samples: u32) -> (Vec<f32>, Vec<usize>) {
+		let library = library();
+		let table = ThoughtSpawnTable::default();
+		let progress = table.progress(score);
+		let weights: Vec<f32> = library.iter().map(|thought| table.weight(thought, progress, &ProgressBar::default())).collect();
+
+		let mut app = spawn_app(library.clone(), table, samples);
+		app.insert_resource(ScoreCounter {score, ..default()}).add_system(spawn_thoughts);
+		for _ in 0..samples / PER_FRAME {
+			app.update();
+		}
+
+		let mut counts = vec![0; library.len()];
+		for thought in app.world.query::<&Thought>().iter(&app.world) {
+			counts[library.iter().position(|known| known == thought).unwrap()] += 1;
+		}
+		assert_eq!(counts.iter().sum::<usize>(), samples as usize);
+		(weights, counts)
+	}
+
+	// Pearson's chi-square against the weights, only over thoughts that can spawn at all
+	fn chi_square(weights: &[f32], counts: &[usize]) -> (f32, usize) {
+		let samples: usize = counts.iter().sum();
+		let total: f32 = weights.iter().sum();
+		let mut statistic = 0.0;
+		let mut categories = 0;
+		for (weight, count) in weights.iter().zip(counts.iter()) {
+			if *weight > 0.0 {
+				let expected = samples as f32 * weight / total;
+				statistic += (*count as f32 - expected).powi(2) / expected;
+				categories += 1;
 			}
 		}
+		(statistic, categories - 1)
+	}
+
+	#[test]
+	fn early_spawns_follow_weights_without_intense_thoughts() {
+		let (weights, counts) = spawned_counts(0, 20000);
+		// Intensity 3 has no weight at the start
+		assert_eq!(weights[4], 0.0);
+		assert_eq!(weights[5], 0.0);
+		assert_eq!(counts[4] + counts[5], 0);
+
+		let (statistic, degrees) = chi_square(&weights, &counts);
+		assert_eq!(degrees, 3);
+		// 99.9th percentile for 3 degrees of freedom
+		assert!(statistic < 16.27, "chi-square {} too large for {:?}", statistic, counts);
+	}
+
+	#[test]
+	fn late_spawns_follow_weights() {
+		let (weights, counts) = spawned_counts(900000, 20000);
+		assert!(counts[4] > 0 && counts[5] > 0);
+		// Intense thoughts are more likely late than mild ones were early
+		assert!(weights[4] > weights[0] * 0.4);
+
+		let (statistic, degrees) = chi_square(&weights, &counts);
+		assert_eq!(degrees, 5);
+		// 99.9th percentile for 5 degrees of freedom
+		assert!(statistic < 20.52, "chi-square {} too large for {:?}", statistic, counts);
+	}
+
+	#[test]
+	fn spawn_despawn_cycles_reuse_assets_and_entities() {
+		let mut app = churn_app();
+		for _ in 0..10 {
+			app.update();
+		}
+		let meshes = app.world.resource::<Assets<Mesh>>().len();
+		let materials = app.world.resource::<Assets<StandardMaterial>>().len();
+		let entities = app.world.entities().len();
+		assert_eq!(meshes, 1);
+		assert_eq!(materials, 3);
+
+		// Thoughts let go of this frame still count until their removals apply, so a batch spawns every other frame
+		let mut spawned = 0;
+		for _ in 0..2000 {
+			app.update();
+			spawned += app.world.query_filtered::<(), With<Thought>>().iter(&app.world).count();
+		}
+		assert!(spawned >= 10_000, "only {} thoughts spawned", spawned);
+		assert_eq!(app.world.resource::<Assets<Mesh>>().len(), meshes);
+		assert_eq!(app.world.resource::<Assets<StandardMaterial>>().len(), materials);
+		assert_eq!(app.world.entities().len(), entities);
 	}
 }
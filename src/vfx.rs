@@ -1,12 +1,13 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, window::PrimaryWindow, asset::LoadState};
 
 use crate::GameState;
-use crate::post_processing::effects::{flip, dither, feedback};
-use crate::post_processing::setup::{EffectOutput, link_texture};
-use crate::post_processing::{spawn_effect, self, link_effect};
+use crate::player::ShipState;
+use crate::post_processing::effects::{flip, dither, feedback, limiter};
+use crate::post_processing::graph::{PostProcessingGraph, despawn_effects};
+use crate::post_processing;
 use crate::post_processing::VFXChangeSystemSet;
 use crate::prelude::ScoreCounter;
-use crate::resources::MainRenderTexture;
+use crate::resources::{MainRenderTexture, RenderResolution, SafetySettings};
 
 pub struct VFXPlugin {}
 
@@ -19,35 +20,114 @@ impl Plugin for VFXPlugin {
 		//.add_plugin(post_processing::EffectPlugin::<jpeg::Encode>::default())
 		//.add_plugin(post_processing::EffectPlugin::<jpeg::Decode>::default())
 		.add_plugin(post_processing::EffectPlugin::<feedback::Effect>::default())
-		.add_system(vfx_setup.in_schedule(OnEnter(GameState::Game)))
+		.add_plugin(post_processing::EffectPlugin::<limiter::Effect>::default())
+		.add_plugin(post_processing::VFXPlugin {})
+		.add_system(follow_window_size.in_base_set(CoreSet::PreUpdate))
+		.init_resource::<BuiltVFXGraph>()
+		.add_startup_system(load_vfx_graph)
+		.add_system(mark_vfx_graph_dirty)
+		.add_system(rebuild_vfx_graph.run_if(in_state(GameState::Game)))
 		.add_system(update_effects.in_set(VFXChangeSystemSet).run_if(in_state(GameState::Game)));
 	}
 }
 
-fn vfx_setup(world: &mut World) {
-	let window = world.query::<&Window>().single(world).clone();
+pub const VFX_GRAPH_PATH: &str = "vfx/default.graph.ron";
 
-	let render_target = world.get_resource::<MainRenderTexture>().expect("There needs to be a MainRenderTexture").clone();
+// The shipped graph as it was at build time, for when the file on disk can't be loaded
+pub fn built_in_graph() -> PostProcessingGraph {
+	ron::from_str(include_str!("../assets/vfx/default.graph.ron")).expect("The built-in post-processing graph should parse")
+}
 
-	let feedback = spawn_effect::<feedback::Effect>(world, 30, EffectOutput::Texture {width: 1280, height: 720});
-	link_texture::<feedback::Effect>(world, render_target.texture, feedback, 0);
+// Keeps the graph asset alive (and watched for changes)
+#[derive(Resource, Debug, Clone)]
+struct VFXGraphHandle(Handle<PostProcessingGraph>);
 
-	let dither = spawn_effect::<dither::Effect>(world, 29, EffectOutput::Texture {width: 640, height: 360});
+// Effects built from the graph, torn down and built again whenever it reloads
+#[derive(Resource, Debug)]
+struct BuiltVFXGraph {
+	effects: Vec<Entity>,
+	dirty: bool,
+}
 
-	link_effect::<dither::Effect>(world, feedback, dither, 0);
-	link_effect::<feedback::Effect>(world, dither, feedback, 1);
+impl Default for BuiltVFXGraph {
+	fn default() -> Self {
+		BuiltVFXGraph {effects: Vec::new(), dirty: true}
+	}
+}
 
-	let flip = spawn_effect::<flip::Effect>(world, 31, EffectOutput::Window {output_window: window});
-	link_effect::<flip::Effect>(world, feedback, flip, 0);
+fn load_vfx_graph(mut commands: Commands, asset_server: Res<AssetServer>) {
+	commands.insert_resource(VFXGraphHandle(asset_server.load(VFX_GRAPH_PATH)));
+}
+
+fn mark_vfx_graph_dirty(
+	mut events: EventReader<AssetEvent<PostProcessingGraph>>,
+	handle: Res<VFXGraphHandle>,
+	safety: Res<SafetySettings>,
+	mut built: ResMut<BuiltVFXGraph>,
+) {
+	// Safe mode decides which nodes get built
+	if safety.is_changed() {
+		built.dirty = true;
+	}
+	for event in events.iter() {
+		match event {
+			AssetEvent::Created {handle: changed} | AssetEvent::Modified {handle: changed} if *changed == handle.0 => {
+				built.dirty = true;
+			},
+			_ => {}
+		}
+	}
+}
+
+// Waits for the graph to load, a broken one leaves whatever was built before it in place
+// One that never loaded at all gets the built-in graph instead, the screen stays black otherwise
+fn rebuild_vfx_graph(world: &mut World) {
+	if !world.resource::<BuiltVFXGraph>().dirty {
+		return;
+	}
+	let handle = world.resource::<VFXGraphHandle>().0.clone();
+	let graph = match world.resource::<Assets<PostProcessingGraph>>().get(&handle).cloned() {
+		Some(graph) => graph,
+		None if world.resource::<AssetServer>().get_load_state(&handle) == LoadState::Failed => {
+			error!("Could not load post-processing graph {}, using the built-in one", VFX_GRAPH_PATH);
+			built_in_graph()
+		},
+		None => return,
+	};
+	world.resource_mut::<BuiltVFXGraph>().dirty = false;
+	let graph = if world.resource::<SafetySettings>().safe_mode {graph} else {graph.without_safe_mode_nodes()};
+
+	match graph.build(world) {
+		Ok(effects) => {
+			info!("Built post-processing graph with {} effects", effects.len());
+			let old = std::mem::replace(&mut world.resource_mut::<BuiltVFXGraph>().effects, effects);
+			despawn_effects(world, &old);
+		},
+		Err(error) => error!("Could not build post-processing graph: {}", error),
+	}
 }
 
 fn update_effects(
 	time: Res<Time>,
 	mut objects: Query<&mut feedback::Effect>,
-	score: Option<ResMut<ScoreCounter>>
+	mut limiters: Query<&mut limiter::Effect>,
+	score: Option<ResMut<ScoreCounter>>,
+	ship_state: Res<State<ShipState>>,
+	safety: Res<SafetySettings>,
 ) {
+	for mut limiter in limiters.iter_mut() {
+		limiter.max_change = safety.colour_step(time.delta_seconds());
+	}
+
+	let hyperspace = match ship_state.0 {
+		ShipState::Cruising => 0.0,
+		ShipState::Hyperspace => 1.0,
+	};
 	for mut e in objects.iter_mut() {
 		e.time = time.elapsed_seconds();
+		// Eases in and out over about a second
+		let step = time.delta_seconds();
+		e.hyperspace += (hyperspace - e.hyperspace).clamp(-step, step);
 	}
 
 	if let Some(score_counter) = score {
@@ -56,7 +136,22 @@ fn update_effects(
 		let x = score_counter.score as f64;
 		let alpha = 0.995 - 0.6 * f64::exp(-5.0 * x / 1000000.0);
 		for mut e in objects.iter_mut() {
-			e.alpha = alpha as f32;
+			e.alpha = safety.feedback_alpha(alpha as f32);
 		}
 	}
 }
+
+// Reallocates the main render texture when the window (or the configured resolution) asks for a different size
+fn follow_window_size(
+	mut main: ResMut<MainRenderTexture>,
+	resolution: Res<RenderResolution>,
+	windows: Query<&Window, With<PrimaryWindow>>,
+	mut images: ResMut<Assets<Image>>,
+) {
+	let window = windows.get_single().ok().map(|window| UVec2::new(window.physical_width(), window.physical_height()));
+	let size = resolution.size(window);
+	if size != main.size() {
+		info!("Resizing main render texture to {}x{}", size.x, size.y);
+		main.resize(&mut images, size);
+	}
+}